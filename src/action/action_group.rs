@@ -0,0 +1,91 @@
+use super::Action;
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+pub struct ActionGroup {
+    actions: Vec<(String, std::sync::Arc<dyn Action>)>,
+}
+
+impl ActionGroup {
+    pub fn new(actions: Vec<(String, std::sync::Arc<dyn Action>)>) -> Result<Self> {
+        if actions.is_empty() {
+            Err(Error(String::from("'actions' cannot be empty.")))
+        } else {
+            Ok(Self { actions })
+        }
+    }
+}
+
+#[async_trait]
+impl Action for ActionGroup {
+    async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
+        let handles: Vec<_> = self
+            .actions
+            .iter()
+            .map(|(name, action)| {
+                let action = action.clone();
+                let placeholders = placeholders.clone();
+                (
+                    name,
+                    tokio::spawn(async move { action.trigger(placeholders).await }),
+                )
+            })
+            .collect();
+        let mut failed = Vec::new();
+        for (name, handle) in handles {
+            match handle.await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => failed.push(format!("'{name}' ({err})")),
+                Err(err) => failed.push(format!("'{name}' ({err})")),
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error(format!(
+                "{} of {} action(s) failed: {}",
+                failed.len(),
+                self.actions.len(),
+                failed.join(", ")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::MockAction;
+
+    fn mock_action(result: Result<()>) -> std::sync::Arc<dyn Action> {
+        let mut mock_action = MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .return_once(move |_| result);
+        std::sync::Arc::new(mock_action)
+    }
+
+    #[tokio::test]
+    async fn test_trigger() {
+        assert!(ActionGroup::new(vec![]).is_err());
+        let group = ActionGroup::new(vec![
+            (String::from("A"), mock_action(Ok(()))),
+            (String::from("B"), mock_action(Ok(()))),
+        ])
+        .unwrap();
+        assert!(group.trigger(PlaceholderMap::new()).await.is_ok());
+        let group = ActionGroup::new(vec![
+            (String::from("A"), mock_action(Ok(()))),
+            (
+                String::from("B"),
+                mock_action(Err(Error(String::from("Unreachable")))),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            group.trigger(PlaceholderMap::new()).await.unwrap_err().0,
+            "1 of 2 action(s) failed: 'B' (Unreachable)"
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue};
+
+use super::Action;
+use crate::config;
+use crate::template::{escape, Template};
+use crate::{Error, PlaceholderMap, Result};
+
+enum Service {
+    Gotify,
+    Matrix { room: String },
+    Ntfy { topic: String, tags: Vec<String> },
+    Slack,
+    Telegram { chat_id: String },
+}
+
+struct Request {
+    method: reqwest::Method,
+    url: String,
+    headers: HeaderMap,
+    body: String,
+}
+
+// Presets for chat and push notification services. They send the same requests a hand-written
+// webhook for the service would.
+pub struct Chat {
+    service: Service,
+    // base URL of the server or URL of the webhook (Slack)
+    url: String,
+    token: Option<String>,
+    title: Template,
+    message: Template,
+    priority: Option<config::ChatPriority>,
+    client: reqwest::Client,
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|x| Error(format!("Could not parse header value: {x}")))
+}
+
+impl Chat {
+    fn new(
+        service: Service,
+        url: &str,
+        token: Option<String>,
+        title: &str,
+        message: &str,
+        priority: Option<config::ChatPriority>,
+        syntax: config::TemplateSyntax,
+    ) -> Result<Self> {
+        if url.is_empty() {
+            Err(Error(String::from("'server' cannot be empty.")))
+        } else if token.as_ref().is_some_and(|x| x.is_empty()) {
+            Err(Error(String::from("'token' cannot be empty.")))
+        } else if message.is_empty() {
+            Err(Error(String::from("'message' cannot be empty.")))
+        } else {
+            Ok(Self {
+                service,
+                url: String::from(url.trim_end_matches('/')),
+                token,
+                title: Template::new(title, syntax, config::Escape::None)
+                    .map_err(|x| Error(format!("Invalid 'title': {x}")))?,
+                message: Template::new(message, syntax, config::Escape::None)
+                    .map_err(|x| Error(format!("Invalid 'message': {x}")))?,
+                priority,
+                client: reqwest::Client::new(),
+            })
+        }
+    }
+
+    // Configured priority or the one that matches the state of the alarm.
+    fn priority(&self, placeholders: &PlaceholderMap) -> config::ChatPriority {
+        self.priority.unwrap_or_else(
+            || match placeholders.get("alarm_state").map(|x| x.as_str()) {
+                Some("Bad") | Some("Error") => config::ChatPriority::High,
+                Some("Good") => config::ChatPriority::Low,
+                _ => config::ChatPriority::Default,
+            },
+        )
+    }
+
+    fn request(&self, placeholders: &PlaceholderMap) -> Result<Request> {
+        use config::ChatPriority;
+        let title = self.title.fill(placeholders);
+        let message = self.message.fill(placeholders);
+        // for services without a separate title
+        let text = if title.is_empty() {
+            message.clone()
+        } else {
+            format!("{title}\n{message}")
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            header_value(&crate::user_agent())?,
+        );
+        let bearer = match &self.token {
+            Some(token) => Some(header_value(&format!("Bearer {token}"))?),
+            None => None,
+        };
+        let token = self.token.as_deref().unwrap_or_default();
+        let (method, url, body) = match &self.service {
+            Service::Gotify => {
+                let priority = match self.priority(placeholders) {
+                    ChatPriority::Low => 2,
+                    ChatPriority::Default => 5,
+                    ChatPriority::High => 8,
+                    ChatPriority::Urgent => 10,
+                };
+                headers.insert("X-Gotify-Key", header_value(token)?);
+                (
+                    reqwest::Method::POST,
+                    format!("{}/message", self.url),
+                    serde_json::json!({
+                        "title": title,
+                        "message": message,
+                        "priority": priority,
+                    })
+                    .to_string(),
+                )
+            }
+            Service::Matrix { room } => {
+                // the same transaction ID for retries and replays lets the server drop
+                // duplicates
+                let transaction_id = match placeholders.get("trigger_id") {
+                    Some(trigger_id) => trigger_id.clone(),
+                    None => format!("{:032x}", rand::random::<u128>()),
+                };
+                headers.extend(bearer.map(|x| (reqwest::header::AUTHORIZATION, x)));
+                (
+                    reqwest::Method::PUT,
+                    format!(
+                        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/minmon-{}",
+                        self.url,
+                        escape(room, config::Escape::Url),
+                        escape(&transaction_id, config::Escape::Url)
+                    ),
+                    serde_json::json!({"msgtype": "m.text", "body": text}).to_string(),
+                )
+            }
+            Service::Ntfy { topic, tags } => {
+                let priority = match self.priority(placeholders) {
+                    ChatPriority::Low => 2,
+                    ChatPriority::Default => 3,
+                    ChatPriority::High => 4,
+                    ChatPriority::Urgent => 5,
+                };
+                headers.extend(bearer.map(|x| (reqwest::header::AUTHORIZATION, x)));
+                (
+                    reqwest::Method::POST,
+                    self.url.clone(),
+                    serde_json::json!({
+                        "topic": topic,
+                        "title": title,
+                        "message": message,
+                        "priority": priority,
+                        "tags": tags,
+                    })
+                    .to_string(),
+                )
+            }
+            Service::Slack => {
+                let text = if title.is_empty() {
+                    message
+                } else {
+                    format!("*{title}*\n{message}")
+                };
+                (
+                    reqwest::Method::POST,
+                    self.url.clone(),
+                    serde_json::json!({"text": text}).to_string(),
+                )
+            }
+            Service::Telegram { chat_id } => {
+                let silent = self.priority(placeholders) == ChatPriority::Low;
+                (
+                    reqwest::Method::POST,
+                    format!("{}/bot{token}/sendMessage", self.url),
+                    serde_json::json!({
+                        "chat_id": chat_id,
+                        "text": text,
+                        "disable_notification": silent,
+                    })
+                    .to_string(),
+                )
+            }
+        };
+        Ok(Request {
+            method,
+            url,
+            headers,
+            body,
+        })
+    }
+}
+
+fn not_empty(name: &str, value: &str) -> Result<String> {
+    if value.is_empty() {
+        Err(Error(format!("'{name}' cannot be empty.")))
+    } else {
+        Ok(String::from(value))
+    }
+}
+
+impl TryFrom<&config::Action> for Chat {
+    type Error = Error;
+
+    fn try_from(action: &config::Action) -> std::result::Result<Self, Self::Error> {
+        let syntax = action.template_syntax;
+        match &action.type_ {
+            config::ActionType::Gotify(gotify) => Self::new(
+                Service::Gotify,
+                &gotify.server,
+                Some(gotify.token.clone()),
+                &gotify.title,
+                &gotify.message,
+                gotify.priority,
+                syntax,
+            ),
+            config::ActionType::Matrix(matrix) => Self::new(
+                Service::Matrix {
+                    room: not_empty("room", &matrix.room)?,
+                },
+                &matrix.server,
+                Some(matrix.token.clone()),
+                &matrix.title,
+                &matrix.message,
+                None,
+                syntax,
+            ),
+            config::ActionType::Ntfy(ntfy) => Self::new(
+                Service::Ntfy {
+                    topic: not_empty("topic", &ntfy.topic)?,
+                    tags: ntfy.tags.clone(),
+                },
+                &ntfy.server,
+                ntfy.token.clone(),
+                &ntfy.title,
+                &ntfy.message,
+                ntfy.priority,
+                syntax,
+            ),
+            config::ActionType::Slack(slack) => Self::new(
+                Service::Slack,
+                &not_empty("url", &slack.url)?,
+                None,
+                &slack.title,
+                &slack.message,
+                None,
+                syntax,
+            ),
+            config::ActionType::Telegram(telegram) => Self::new(
+                Service::Telegram {
+                    chat_id: not_empty("chat_id", &telegram.chat_id)?,
+                },
+                &telegram.server,
+                Some(telegram.token.clone()),
+                &telegram.title,
+                &telegram.message,
+                telegram.priority,
+                syntax,
+            ),
+            _ => panic!(),
+        }
+    }
+}
+
+#[async_trait]
+impl Action for Chat {
+    async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
+        let request = self.request(&placeholders)?;
+        super::webhook::send(
+            self.client
+                .request(request.method, &request.url)
+                .headers(request.headers)
+                .body(request.body),
+            &super::webhook::ResponseCheck::default(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn chat(options: &str) -> Chat {
+        let config = config::Config::try_from(
+            format!(
+                r#"
+                [[actions]]
+                name = "Chat"
+                {options}
+                "#
+            )
+            .as_str(),
+        )
+        .unwrap();
+        Chat::try_from(config.actions.first().unwrap()).unwrap()
+    }
+
+    fn placeholders(alarm_state: &str) -> PlaceholderMap {
+        PlaceholderMap::from([
+            (String::from("check_name"), String::from("Disk")),
+            (String::from("alarm_state"), String::from(alarm_state)),
+        ])
+    }
+
+    // Accepts one request, answers with the status and returns the request as text.
+    async fn stand_in(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = Vec::new();
+            loop {
+                let mut chunk = [0; 1024];
+                let count = stream.read(&mut chunk).await.unwrap();
+                buffer.extend_from_slice(&chunk[..count]);
+                let text = String::from_utf8_lossy(&buffer).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|x| x.strip_prefix("content-length:"))
+                        .map(|x| x.trim().parse::<usize>().unwrap())
+                        .unwrap_or_default();
+                    if buffer.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if count == 0 {
+                    break;
+                }
+            }
+            let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(buffer).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_ntfy() {
+        let (url, handle) = stand_in("200 OK").await;
+        let ntfy = chat(&format!(
+            r#"
+            type = "Ntfy"
+            server = "{url}/"
+            topic = "alerts"
+            token = "secret"
+            title = "{{{{check_name}}}} is {{{{alarm_state}}}}"
+            message = "Line 1\nLine \"2\""
+            tags = ["warning"]
+            "#
+        ));
+        ntfy.trigger(placeholders("Bad")).await.unwrap();
+        let request = handle.await.unwrap();
+        assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer secret\r\n"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        assert_eq!(
+            json(body),
+            serde_json::json!({
+                "topic": "alerts",
+                "title": "Disk is Bad",
+                "message": "Line 1\nLine \"2\"",
+                "priority": 4,
+                "tags": ["warning"],
+            })
+        );
+        // errors are reported
+        let (url, handle) = stand_in("500 Internal Server Error").await;
+        let ntfy = chat(&format!(
+            r#"
+            type = "Ntfy"
+            server = "{url}"
+            topic = "alerts"
+            message = "foo"
+            "#
+        ));
+        assert!(ntfy.trigger(placeholders("Bad")).await.is_err());
+        handle.await.unwrap();
+    }
+
+    fn json(body: &str) -> serde_json::Value {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_requests() {
+        let gotify = chat(
+            r#"
+            type = "Gotify"
+            server = "https://gotify.example.com"
+            token = "secret"
+            message = "{{check_name}}"
+            "#,
+        );
+        let request = gotify.request(&placeholders("Good")).unwrap();
+        assert_eq!(request.method, reqwest::Method::POST);
+        assert_eq!(request.url, "https://gotify.example.com/message");
+        assert_eq!(request.headers.get("X-Gotify-Key").unwrap(), "secret");
+        assert_eq!(
+            json(&request.body),
+            serde_json::json!({"title": "", "message": "Disk", "priority": 2})
+        );
+
+        let matrix = chat(
+            r#"
+            type = "Matrix"
+            server = "https://matrix.example.com"
+            token = "secret"
+            room = "!room:example.com"
+            title = "Alarm"
+            message = "{{check_name}}"
+            "#,
+        );
+        let first = matrix.request(&placeholders("Bad")).unwrap();
+        assert_eq!(first.method, reqwest::Method::PUT);
+        assert!(first.url.starts_with("https://matrix.example.com/_matrix/client/v3/rooms/%21room%3Aexample.com/send/m.room.message/minmon-"));
+        assert_eq!(
+            json(&first.body),
+            serde_json::json!({"msgtype": "m.text", "body": "Alarm\nDisk"})
+        );
+        assert_ne!(matrix.request(&placeholders("Bad")).unwrap().url, first.url);
+        let mut retried = placeholders("Bad");
+        retried.insert(String::from("trigger_id"), String::from("abc"));
+        let url = matrix.request(&retried).unwrap().url;
+        assert!(url.ends_with("/send/m.room.message/minmon-abc"));
+        assert_eq!(matrix.request(&retried).unwrap().url, url);
+
+        let slack = chat(
+            r#"
+            type = "Slack"
+            url = "https://hooks.example.com/abc"
+            title = "Alarm"
+            message = "{{check_name}}"
+            "#,
+        );
+        let request = slack.request(&placeholders("Bad")).unwrap();
+        assert_eq!(request.url, "https://hooks.example.com/abc");
+        assert_eq!(
+            json(&request.body),
+            serde_json::json!({"text": "*Alarm*\nDisk"})
+        );
+
+        let telegram = chat(
+            r#"
+            type = "Telegram"
+            token = "123:abc"
+            chat_id = "-100"
+            message = "{{check_name}}"
+            "#,
+        );
+        let request = telegram.request(&placeholders("Good")).unwrap();
+        assert_eq!(
+            request.url,
+            "https://api.telegram.org/bot123:abc/sendMessage"
+        );
+        assert_eq!(
+            json(&request.body),
+            serde_json::json!({"chat_id": "-100", "text": "Disk", "disable_notification": true})
+        );
+    }
+}
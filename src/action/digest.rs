@@ -0,0 +1,224 @@
+use super::Action;
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+#[derive(Default)]
+struct Batch {
+    open: bool,
+    // placeholders of the first trigger of the batch
+    placeholders: Option<PlaceholderMap>,
+    items: Vec<String>,
+}
+
+pub struct Digest {
+    action: std::sync::Arc<dyn Action>,
+    window: std::time::Duration,
+    item_template: String,
+    separator: String,
+    send_first: bool,
+    batch: std::sync::Arc<std::sync::Mutex<Batch>>,
+}
+
+impl Digest {
+    pub fn new(
+        action: std::sync::Arc<dyn Action>,
+        window: std::time::Duration,
+        item_template: String,
+        separator: String,
+        send_first: bool,
+    ) -> Result<Self> {
+        if window.is_zero() {
+            Err(Error(String::from("'window' cannot be 0.")))
+        } else if item_template.is_empty() {
+            Err(Error(String::from("'item_template' cannot be empty.")))
+        } else {
+            Ok(Self {
+                action,
+                window,
+                item_template,
+                separator,
+                send_first,
+                batch: std::sync::Arc::new(std::sync::Mutex::new(Batch::default())),
+            })
+        }
+    }
+
+    fn digest_placeholders(
+        mut placeholders: PlaceholderMap,
+        items: &[String],
+        separator: &str,
+    ) -> PlaceholderMap {
+        placeholders.insert(String::from("digest"), items.join(separator));
+        placeholders.insert(String::from("digest_count"), items.len().to_string());
+        placeholders
+    }
+
+    // Sends the collected triggers when the window closes.
+    fn spawn_flush(&self) {
+        let action = self.action.clone();
+        let window = self.window;
+        let separator = self.separator.clone();
+        let batch = self.batch.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            let batch = std::mem::take(&mut *batch.lock().unwrap());
+            if let Some(placeholders) = batch.placeholders {
+                let placeholders =
+                    Self::digest_placeholders(placeholders, &batch.items, &separator);
+                if let Err(err) = action.trigger(placeholders).await {
+                    log::error!("Digest could not be sent: {err}");
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Action for Digest {
+    async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
+        let item = crate::fill_placeholders(&self.item_template, &placeholders);
+        let send_now = {
+            let mut batch = self.batch.lock().unwrap();
+            let first = !batch.open;
+            batch.open = true;
+            if first && self.send_first {
+                true
+            } else {
+                batch.items.push(item.clone());
+                batch.placeholders.get_or_insert(placeholders.clone());
+                if first {
+                    self.spawn_flush();
+                }
+                false
+            }
+        };
+        if send_now {
+            self.spawn_flush();
+            self.action
+                .trigger(Self::digest_placeholders(
+                    placeholders,
+                    &[item],
+                    &self.separator,
+                ))
+                .await
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::MockAction;
+    use mockall::predicate::*;
+
+    fn placeholders(id: &str) -> PlaceholderMap {
+        PlaceholderMap::from([(String::from("check_id"), String::from(id))])
+    }
+
+    fn digest_placeholders(id: &str, digest: &str, count: &str) -> PlaceholderMap {
+        let mut res = placeholders(id);
+        res.insert(String::from("digest"), String::from(digest));
+        res.insert(String::from("digest_count"), String::from(count));
+        res
+    }
+
+    #[tokio::test]
+    async fn test_digest() {
+        let mut mock_action = MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .with(eq(digest_placeholders("a", "- a\n- b\n- c", "3")))
+            .returning(|_| Ok(()));
+        let millis = std::time::Duration::from_millis;
+        let digest = Digest::new(
+            std::sync::Arc::new(mock_action),
+            millis(50),
+            String::from("- {{check_id}}"),
+            String::from("\n"),
+            false,
+        )
+        .unwrap();
+        for id in ["a", "b", "c"] {
+            digest.trigger(placeholders(id)).await.unwrap();
+        }
+        tokio::time::sleep(millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn test_send_first() {
+        let mut mock_action = MockAction::new();
+        let mut seq = mockall::Sequence::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(eq(digest_placeholders("a", "a", "1")))
+            .returning(|_| Ok(()));
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(eq(digest_placeholders("b", "b, c", "2")))
+            .returning(|_| Ok(()));
+        let millis = std::time::Duration::from_millis;
+        let digest = Digest::new(
+            std::sync::Arc::new(mock_action),
+            millis(50),
+            String::from("{{check_id}}"),
+            String::from(", "),
+            true,
+        )
+        .unwrap();
+        for id in ["a", "b", "c"] {
+            digest.trigger(placeholders(id)).await.unwrap();
+        }
+        tokio::time::sleep(millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let mut mock_action = MockAction::new();
+        let mut seq = mockall::Sequence::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .returning(|_| Err(Error(String::from("Unreachable"))));
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(function(|placeholders: &PlaceholderMap| {
+                placeholders.get("digest").unwrap() == "a, b"
+                    && placeholders.get("action_attempt").unwrap() == "2"
+            }))
+            .returning(|_| Ok(()));
+        let millis = std::time::Duration::from_millis;
+        let sender = crate::action::ActionBase::new(
+            String::from("Name"),
+            std::time::Duration::from_secs(1),
+            PlaceholderMap::new(),
+            Some(crate::action::RetryPolicy::new(2, millis(10), 1.0, millis(10)).unwrap()),
+            vec![],
+            None,
+            None,
+            mock_action,
+        )
+        .unwrap();
+        let digest = Digest::new(
+            std::sync::Arc::new(sender),
+            millis(50),
+            String::from("{{check_id}}"),
+            String::from(", "),
+            false,
+        )
+        .unwrap();
+        for id in ["a", "b"] {
+            digest.trigger(placeholders(id)).await.unwrap();
+        }
+        tokio::time::sleep(millis(150)).await;
+    }
+}
@@ -1,19 +1,75 @@
 use super::Action;
 use crate::config;
+use crate::template::Template;
 use crate::{Error, PlaceholderMap, Result};
 use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
 
 pub struct Email {
-    from: lettre::message::Mailbox,
-    to: lettre::message::Mailbox,
-    reply_to: Option<lettre::message::Mailbox>,
-    subject: String,
-    body: String,
-    smtp_server: String,
-    smtp_port: Option<u16>,
-    smtp_security: config::SmtpSecurity,
-    username: String,
-    password: String,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    cc: Vec<Mailbox>,
+    bcc: Vec<Mailbox>,
+    reply_to: Option<Mailbox>,
+    subject: Template,
+    body: Template,
+    html_body: Option<Template>,
+    mailer_builder: lettre::transport::smtp::AsyncSmtpTransportBuilder,
+    // The mailer keeps a pool of connections that are reused across triggers. It is created on
+    // first use because the pool needs a running runtime.
+    mailer: std::sync::OnceLock<AsyncSmtpTransport<Tokio1Executor>>,
+}
+
+fn parse_mailboxes(addresses: &[String], kind: &str) -> Result<Vec<Mailbox>> {
+    addresses
+        .iter()
+        .map(|x| {
+            x.parse()
+                .map_err(|x| Error(format!("Invalid {kind} email address: {x}")))
+        })
+        .collect()
+}
+
+fn mailer_builder(
+    email: &config::ActionEmail,
+) -> Result<lettre::transport::smtp::AsyncSmtpTransportBuilder> {
+    use lettre::transport::smtp::authentication::Credentials;
+
+    let mut mailer_builder = match email.smtp_security {
+        config::SmtpSecurity::TLS => {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&email.smtp_server)
+                .map_err(|x| Error(x.to_string()))
+        }
+        config::SmtpSecurity::STARTTLS => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.smtp_server)
+                .map_err(|x| Error(x.to_string()))
+        }
+        config::SmtpSecurity::Plain => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+            &email.smtp_server,
+        )),
+    }?;
+    match (&email.username, &email.password) {
+        (Some(username), Some(password)) => {
+            if username.is_empty() {
+                return Err(Error(String::from("'username' cannot be empty.")));
+            } else if password.is_empty() {
+                return Err(Error(String::from("'password' cannot be empty.")));
+            }
+            mailer_builder =
+                mailer_builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error(String::from(
+                "'username' and 'password' must be set together.",
+            )))
+        }
+    }
+    if let Some(port) = email.smtp_port {
+        mailer_builder = mailer_builder.port(port);
+    }
+    Ok(mailer_builder)
 }
 
 impl TryFrom<&config::Action> for Email {
@@ -21,38 +77,48 @@ impl TryFrom<&config::Action> for Email {
 
     fn try_from(action: &config::Action) -> std::result::Result<Self, Self::Error> {
         if let config::ActionType::Email(email) = &action.type_ {
-            if email.subject.is_empty() {
+            let to = email.to.to_vec();
+            if to.is_empty() {
+                Err(Error(String::from("'to' cannot be empty.")))
+            } else if email.subject.is_empty() {
                 Err(Error(String::from("'subject' cannot be empty.")))
             } else if email.body.is_empty() {
                 Err(Error(String::from("'body' cannot be empty.")))
+            } else if email.html_body.as_ref().is_some_and(|x| x.is_empty()) {
+                Err(Error(String::from("'html_body' cannot be empty.")))
             } else if email.smtp_server.is_empty() {
                 Err(Error(String::from("'smtp_server' cannot be empty.")))
-            } else if email.username.is_empty() {
-                Err(Error(String::from("'username' cannot be empty.")))
-            } else if email.password.is_empty() {
-                Err(Error(String::from("'password' cannot be empty.")))
             } else {
                 Ok(Self {
                     from: email
                         .from
                         .parse()
                         .map_err(|x| Error(format!("Invalid sender email address: {x}")))?,
-                    to: email
-                        .to
-                        .parse()
-                        .map_err(|x| Error(format!("Invalid recipient email address: {x}")))?,
+                    to: parse_mailboxes(&to, "recipient")?,
+                    cc: parse_mailboxes(&email.cc, "cc")?,
+                    bcc: parse_mailboxes(&email.bcc, "bcc")?,
                     reply_to: email.reply_to.as_ref().map_or(Ok(None), |x| {
                         Ok(Some(x.parse().map_err(|x| {
                             Error(format!("Invalid reply-to email address: {x}"))
                         })?))
                     })?,
-                    subject: email.subject.clone(),
-                    body: email.body.clone(),
-                    smtp_server: email.smtp_server.clone(),
-                    smtp_port: email.smtp_port,
-                    smtp_security: email.smtp_security,
-                    username: email.username.clone(),
-                    password: email.password.clone(),
+                    subject: Template::new(
+                        &email.subject,
+                        action.template_syntax,
+                        config::Escape::None,
+                    )
+                    .map_err(|x| Error(format!("Invalid 'subject': {x}")))?,
+                    body: Template::new(&email.body, action.template_syntax, config::Escape::None)
+                        .map_err(|x| Error(format!("Invalid 'body': {x}")))?,
+                    html_body: match &email.html_body {
+                        Some(html_body) => Some(
+                            Template::new(html_body, action.template_syntax, config::Escape::Html)
+                                .map_err(|x| Error(format!("Invalid 'html_body': {x}")))?,
+                        ),
+                        None => None,
+                    },
+                    mailer_builder: mailer_builder(email)?,
+                    mailer: std::sync::OnceLock::new(),
                 })
             }
         } else {
@@ -64,42 +130,38 @@ impl TryFrom<&config::Action> for Email {
 #[async_trait]
 impl Action for Email {
     async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
-        use lettre::transport::smtp::authentication::Credentials;
-        use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+        use lettre::message::MultiPart;
+        use lettre::{AsyncTransport, Message};
 
-        let subject = crate::fill_placeholders(self.subject.as_str(), &placeholders);
-        let body = crate::fill_placeholders(self.body.as_str(), &placeholders);
+        let subject = self.subject.fill(&placeholders);
+        let body = self.body.fill(&placeholders);
         let mut message_builder = Message::builder()
             .from(self.from.clone())
-            .to(self.to.clone())
             .subject(&subject)
             .user_agent(crate::user_agent());
+        for to in self.to.iter() {
+            message_builder = message_builder.to(to.clone());
+        }
+        for cc in self.cc.iter() {
+            message_builder = message_builder.cc(cc.clone());
+        }
+        for bcc in self.bcc.iter() {
+            message_builder = message_builder.bcc(bcc.clone());
+        }
         if let Some(reply_to) = &self.reply_to {
             message_builder = message_builder.reply_to(reply_to.clone());
         }
-        let email = message_builder
-            .body(body)
-            .map_err(|x| Error(x.to_string()))?;
-        let credentials = Credentials::new(self.username.clone(), self.password.clone());
-        let mut mailer_builder = match self.smtp_security {
-            config::SmtpSecurity::TLS => {
-                AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_server)
-                    .map_err(|x| Error(x.to_string()))
-            }
-            config::SmtpSecurity::STARTTLS => {
-                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_server)
-                    .map_err(|x| Error(x.to_string()))
-            }
-            config::SmtpSecurity::Plain => Ok(
-                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.smtp_server),
-            ),
-        }?;
-
-        mailer_builder = mailer_builder.credentials(credentials);
-        if let Some(port) = self.smtp_port {
-            mailer_builder = mailer_builder.port(port);
+        let email = match &self.html_body {
+            Some(html_body) => message_builder.multipart(MultiPart::alternative_plain_html(
+                body,
+                html_body.fill(&placeholders),
+            )),
+            None => message_builder.body(body),
         }
-        let mailer = mailer_builder.build();
+        .map_err(|x| Error(x.to_string()))?;
+        let mailer = self
+            .mailer
+            .get_or_init(|| self.mailer_builder.clone().build());
         mailer
             .send(email)
             .await
@@ -107,3 +169,167 @@ impl Action for Email {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    // Minimal SMTP server that accepts everything. Returns its port, the received messages and
+    // the number of reused connections (the pool checks them with a NOOP before reusing them).
+    async fn smtp_sink() -> (
+        u16,
+        std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+        std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reuses = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (messages_clone, reuses_clone) = (messages.clone(), reuses.clone());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let messages = messages_clone.clone();
+                let reuses = reuses_clone.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = tokio::io::BufReader::new(reader).lines();
+                    writer.write_all(b"220 localhost\r\n").await.unwrap();
+                    // envelope and data of the current message
+                    let mut message = String::new();
+                    let mut data = false;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let response = if data {
+                            if line == "." {
+                                data = false;
+                                messages.lock().unwrap().push(std::mem::take(&mut message));
+                                "250 OK"
+                            } else {
+                                message.push_str(&line);
+                                message.push('\n');
+                                continue;
+                            }
+                        } else if line.starts_with("EHLO") {
+                            "250 localhost"
+                        } else if line.starts_with("RCPT") {
+                            message.push_str(&line);
+                            message.push('\n');
+                            "250 OK"
+                        } else if line.starts_with("DATA") {
+                            data = true;
+                            "354 Go ahead"
+                        } else if line.starts_with("NOOP") {
+                            reuses.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            "250 OK"
+                        } else if line.starts_with("QUIT") {
+                            writer.write_all(b"221 Bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            "250 OK"
+                        };
+                        writer
+                            .write_all(format!("{response}\r\n").as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        (port, messages, reuses)
+    }
+
+    #[tokio::test]
+    async fn test_email() {
+        let (port, messages, reuses) = smtp_sink().await;
+        let config = config::Config::try_from(
+            format!(
+                r#"
+                [[actions]]
+                name = "Email"
+                type = "Email"
+                from = "minmon@example.com"
+                to = ["a@example.com", "b@example.com"]
+                cc = ["c@example.com"]
+                bcc = ["d@example.com"]
+                subject = "Alarm"
+                body = "Check {{{{check_name}}}} failed."
+                html_body = "<p>Check <b>{{{{check_name}}}}</b> failed.</p>"
+                smtp_server = "127.0.0.1"
+                smtp_port = {port}
+                smtp_security = "Plain"
+                "#
+            )
+            .as_str(),
+        )
+        .unwrap();
+        let email = Email::try_from(config.actions.first().unwrap()).unwrap();
+        let placeholders =
+            PlaceholderMap::from([(String::from("check_name"), String::from("<Disk>"))]);
+        email.trigger(placeholders.clone()).await.unwrap();
+        // connections are put back into the pool in the background, so sending again might use a
+        // new connection until then
+        for _ in 0..100 {
+            email.trigger(placeholders.clone()).await.unwrap();
+            if reuses.load(std::sync::atomic::Ordering::Relaxed) > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        // the connection is reused
+        assert!(reuses.load(std::sync::atomic::Ordering::Relaxed) > 0);
+        let messages = messages.lock().unwrap();
+        assert!(messages.len() >= 2);
+        let message = messages.first().unwrap();
+        for recipient in ["a", "b", "c", "d"] {
+            assert!(message.contains(&format!("RCPT TO:<{recipient}@example.com>")));
+        }
+        assert!(message.contains("To: a@example.com, b@example.com\n"));
+        assert!(message.contains("Cc: c@example.com\n"));
+        assert!(!message.contains("Bcc:"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("Check <Disk> failed."));
+        assert!(message.contains("<p>Check <b>&lt;Disk&gt;</b> failed.</p>"));
+    }
+
+    #[test]
+    fn test_validation() {
+        let email = |options: &str| {
+            let config = config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "Email"
+                    type = "Email"
+                    from = "minmon@example.com"
+                    subject = "Alarm"
+                    body = "Alarm"
+                    smtp_server = "localhost"
+                    {options}
+                    "#
+                )
+                .as_str(),
+            )
+            .unwrap();
+            Email::try_from(config.actions.first().unwrap()).map(|_| ())
+        };
+        assert!(email(r#"to = "a@example.com""#).is_ok());
+        assert!(email("to = []").is_err());
+        assert!(email(r#"to = ["foo"]"#).is_err());
+        assert!(email(
+            r#"
+            to = "a@example.com"
+            username = "user"
+            "#
+        )
+        .is_err());
+        assert!(email(
+            r#"
+            to = "a@example.com"
+            username = "user"
+            password = "secret"
+            "#
+        )
+        .is_ok());
+    }
+}
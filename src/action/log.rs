@@ -1,11 +1,12 @@
 use super::Action;
 use crate::config;
+use crate::template::Template;
 use crate::{Error, PlaceholderMap, Result};
 use async_trait::async_trait;
 
 pub struct Log {
     level: log::Level,
-    template: String,
+    template: Template,
 }
 
 impl TryFrom<&config::Action> for Log {
@@ -18,7 +19,12 @@ impl TryFrom<&config::Action> for Log {
             } else {
                 Ok(Self {
                     level: log.level.into(),
-                    template: log.template.clone(),
+                    template: Template::new(
+                        &log.template,
+                        action.template_syntax,
+                        config::Escape::None,
+                    )
+                    .map_err(|x| Error(format!("Invalid 'template': {x}")))?,
                 })
             }
         } else {
@@ -30,7 +36,7 @@ impl TryFrom<&config::Action> for Log {
 #[async_trait]
 impl Action for Log {
     async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
-        let text = crate::fill_placeholders(self.template.as_str(), &placeholders);
+        let text = self.template.fill(&placeholders);
         log::log!(self.level, "{}", text);
         Ok(())
     }
@@ -1,28 +1,105 @@
 use crate::config;
+use crate::outbox::{Outbox, Replay};
 use crate::ActionMap;
 use crate::{Error, PlaceholderMap, Result};
 use async_trait::async_trait;
 extern crate log as log_ext;
 
+mod action_group;
+#[cfg(feature = "http")]
+mod chat;
+mod digest;
 #[cfg(feature = "smtp")]
 mod email;
 mod log;
+#[cfg(feature = "mqtt")]
+mod mqtt;
 mod process;
+mod throttle;
 #[cfg(feature = "http")]
 mod webhook;
 pub use self::log::Log;
+pub use action_group::ActionGroup;
+#[cfg(feature = "http")]
+pub use chat::Chat;
+pub use digest::Digest;
 #[cfg(feature = "smtp")]
 pub use email::Email;
+#[cfg(feature = "mqtt")]
+pub use mqtt::Mqtt;
 pub use process::Process;
+pub use throttle::Throttle;
 #[cfg(feature = "http")]
 pub use webhook::Webhook;
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait Action: Send + Sync {
+    // Called once at startup, before the action is triggered for the first time.
+    fn start(&self) -> Result<()> {
+        Ok(())
+    }
     async fn trigger(&self, mut placeholders: PlaceholderMap) -> Result<()>;
 }
 
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: std::time::Duration,
+    backoff_factor: f64,
+    max_delay: std::time::Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(
+        max_attempts: u32,
+        initial_delay: std::time::Duration,
+        backoff_factor: f64,
+        max_delay: std::time::Duration,
+    ) -> Result<Self> {
+        if max_attempts == 0 {
+            Err(Error(String::from("'max_attempts' cannot be 0.")))
+        } else if !(backoff_factor >= 1.0 && backoff_factor.is_finite()) {
+            Err(Error(String::from(
+                "'backoff_factor' must be a number greater than or equal to 1.",
+            )))
+        } else if max_delay < initial_delay {
+            Err(Error(String::from(
+                "'max_delay' cannot be less than 'initial_delay'.",
+            )))
+        } else {
+            Ok(Self {
+                max_attempts,
+                initial_delay,
+                backoff_factor,
+                max_delay,
+            })
+        }
+    }
+
+    // Delay after the given (failed) attempt, starting at 1.
+    fn delay(&self, attempt: u32) -> std::time::Duration {
+        let delay = self.initial_delay.as_secs_f64()
+            * self
+                .backoff_factor
+                .powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+        std::time::Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+}
+
+impl TryFrom<&config::ActionRetry> for RetryPolicy {
+    type Error = Error;
+
+    fn try_from(retry: &config::ActionRetry) -> std::result::Result<Self, Self::Error> {
+        Self::new(
+            retry.max_attempts,
+            std::time::Duration::from_secs(retry.initial_delay.into()),
+            retry.backoff_factor,
+            std::time::Duration::from_secs(retry.max_delay.into()),
+        )
+    }
+}
+
 pub struct ActionBase<T>
 where
     T: Action,
@@ -30,7 +107,11 @@ where
     name: String,
     timeout: std::time::Duration,
     placeholders: PlaceholderMap,
-    action: T,
+    retry: Option<RetryPolicy>,
+    fallback: Vec<std::sync::Arc<dyn Action>>,
+    throttle: Option<Throttle>,
+    outbox: Option<std::sync::Arc<Outbox>>,
+    action: std::sync::Arc<T>,
 }
 
 impl<T> ActionBase<T>
@@ -41,6 +122,10 @@ where
         name: String,
         timeout: std::time::Duration,
         placeholders: PlaceholderMap,
+        retry: Option<RetryPolicy>,
+        fallback: Vec<std::sync::Arc<dyn Action>>,
+        throttle: Option<Throttle>,
+        outbox: Option<std::sync::Arc<Outbox>>,
         action: T,
     ) -> Result<Self> {
         if name.is_empty() {
@@ -52,7 +137,11 @@ where
                 name,
                 timeout,
                 placeholders,
-                action,
+                retry,
+                fallback,
+                throttle,
+                outbox,
+                action: std::sync::Arc::new(action),
             })
         }
     }
@@ -61,14 +150,155 @@ where
         placeholders.insert(String::from("action_name"), self.name.clone());
         crate::merge_placeholders(placeholders, &self.placeholders);
     }
+
+    async fn trigger_attempt(
+        name: &str,
+        timeout: std::time::Duration,
+        action: &T,
+        mut placeholders: PlaceholderMap,
+        attempt: u32,
+    ) -> Result<()> {
+        placeholders.insert(String::from("action_attempt"), attempt.to_string());
+        let res = tokio::time::timeout(timeout, action.trigger(placeholders)).await;
+        match res {
+            Ok(inner) => inner,
+            Err(_) => Err(Error(format!(
+                "Action '{}' timed out after {} seconds.",
+                name,
+                timeout.as_secs()
+            ))),
+        }
+    }
+}
+
+impl<T> ActionBase<T>
+where
+    T: Action + 'static,
+{
+    // Retries run in the background so the check loop is not blocked.
+    fn spawn_retries(
+        &self,
+        retry: RetryPolicy,
+        placeholders: PlaceholderMap,
+        fallback_placeholders: PlaceholderMap,
+        entry: Option<std::path::PathBuf>,
+    ) {
+        let name = self.name.clone();
+        let timeout = self.timeout;
+        let action = self.action.clone();
+        let fallback = self.fallback.clone();
+        let outbox = self.outbox.clone();
+        tokio::spawn(async move {
+            for attempt in 2..=retry.max_attempts {
+                tokio::time::sleep(retry.delay(attempt - 1)).await;
+                log_ext::info!(
+                    "Retrying action '{}' (attempt {} of {}).",
+                    name,
+                    attempt,
+                    retry.max_attempts
+                );
+                match Self::trigger_attempt(&name, timeout, &action, placeholders.clone(), attempt)
+                    .await
+                {
+                    Ok(()) => {
+                        finish_outbox(outbox.as_ref(), &name, entry.as_deref(), true);
+                        return;
+                    }
+                    Err(err) if attempt < retry.max_attempts => log_ext::warn!(
+                        "Action '{}' failed (attempt {} of {}): {}",
+                        name,
+                        attempt,
+                        retry.max_attempts,
+                        err
+                    ),
+                    Err(err) => {
+                        log_ext::error!(
+                            "Action '{}' failed after {} attempts: {}",
+                            name,
+                            attempt,
+                            err
+                        );
+                        let res =
+                            trigger_fallback(&name, &fallback, fallback_placeholders.clone(), err)
+                                .await;
+                        finish_outbox(outbox.as_ref(), &name, entry.as_deref(), res.is_ok());
+                        if let Err(err) = res {
+                            log_ext::error!("{err}");
+                        }
+                    }
+                }
+            }
+        });
+    }
 }
 
 #[async_trait]
 impl<T> Action for ActionBase<T>
 where
-    T: Action,
+    T: Action + 'static,
 {
+    fn start(&self) -> Result<()> {
+        self.action
+            .start()
+            .map_err(|x| Error(format!("Action '{}' failed to start: {x}", self.name)))
+    }
+
     async fn trigger(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if let Some(throttle) = &self.throttle {
+            match throttle.admit(&placeholders) {
+                Ok(suppressed_count) => {
+                    placeholders.insert(
+                        String::from("suppressed_count"),
+                        suppressed_count.to_string(),
+                    );
+                }
+                Err(reason) => {
+                    log_ext::info!("Action '{}' suppressed ({}).", self.name, reason);
+                    return Ok(());
+                }
+            }
+        }
+        // identifies the trigger across retries and replays from the outbox
+        placeholders.insert(
+            String::from("trigger_id"),
+            format!("{:032x}", rand::random::<u128>()),
+        );
+        let entry =
+            self.outbox
+                .as_ref()
+                .and_then(|outbox| match outbox.store(&self.name, &placeholders) {
+                    Ok(path) => Some(path),
+                    Err(err) => {
+                        log_ext::error!("Action '{}' could not use the outbox: {}", self.name, err);
+                        None
+                    }
+                });
+        self.deliver(placeholders, entry).await
+    }
+}
+
+#[async_trait]
+impl<T> Replay for ActionBase<T>
+where
+    T: Action + 'static,
+{
+    async fn replay(&self, path: std::path::PathBuf, placeholders: PlaceholderMap) {
+        if let Err(err) = self.deliver(placeholders, Some(path)).await {
+            log_ext::error!("{err}");
+        }
+    }
+}
+
+impl<T> ActionBase<T>
+where
+    T: Action + 'static,
+{
+    async fn deliver(
+        &self,
+        mut placeholders: PlaceholderMap,
+        entry: Option<std::path::PathBuf>,
+    ) -> Result<()> {
+        let fallback_placeholders = placeholders.clone();
         self.add_placeholders(&mut placeholders);
         if placeholders.contains_key("event_name") {
             log_ext::info!(
@@ -76,6 +306,12 @@ where
                 placeholders.get("action_name").unwrap(),
                 placeholders.get("event_name").unwrap()
             );
+        } else if placeholders.contains_key("composite_name") {
+            log_ext::info!(
+                "Action '{}' triggered for composite alarm '{}'.",
+                placeholders.get("action_name").unwrap(),
+                placeholders.get("composite_name").unwrap()
+            );
         } else {
             log_ext::info!(
                 "Action '{}' triggered for alarm '{}', id '{}' from check '{}'.",
@@ -85,18 +321,104 @@ where
                 placeholders.get("check_name").unwrap()
             );
         }
-        let res = tokio::time::timeout(self.timeout, self.action.trigger(placeholders)).await;
-        match res {
-            Ok(inner) => inner,
-            Err(_) => Err(Error(format!(
-                "Action '{}' timed out after {} seconds.",
-                self.name,
-                self.timeout.as_secs()
-            ))),
+        let res = Self::trigger_attempt(
+            &self.name,
+            self.timeout,
+            &self.action,
+            placeholders.clone(),
+            1,
+        )
+        .await;
+        match (res, self.retry) {
+            (Err(err), Some(retry)) if retry.max_attempts > 1 => {
+                log_ext::warn!(
+                    "Action '{}' failed (attempt 1 of {}): {}",
+                    self.name,
+                    retry.max_attempts,
+                    err
+                );
+                self.spawn_retries(retry, placeholders, fallback_placeholders, entry);
+                Ok(())
+            }
+            (Err(err), _) => {
+                let res =
+                    trigger_fallback(&self.name, &self.fallback, fallback_placeholders, err).await;
+                finish_outbox(
+                    self.outbox.as_ref(),
+                    &self.name,
+                    entry.as_deref(),
+                    res.is_ok(),
+                );
+                res
+            }
+            (res, _) => {
+                finish_outbox(self.outbox.as_ref(), &self.name, entry.as_deref(), true);
+                res
+            }
+        }
+    }
+}
+
+// A delivered action also replays its pending entries, it might have been offline before.
+fn finish_outbox(
+    outbox: Option<&std::sync::Arc<Outbox>>,
+    name: &str,
+    entry: Option<&std::path::Path>,
+    delivered: bool,
+) {
+    if let (Some(outbox), Some(entry)) = (outbox, entry) {
+        outbox.finish(entry, delivered);
+        if delivered {
+            let outbox = outbox.clone();
+            let name = String::from(name);
+            tokio::spawn(async move { outbox.replay(Some(&name)).await });
         }
     }
 }
 
+// Reads certificates and keys for the TLS connections of the actions.
+#[cfg(all(
+    any(feature = "http", feature = "mqtt"),
+    any(feature = "__tls", feature = "__rustls")
+))]
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|x| Error(format!("Could not read {path}: {x}")))
+}
+
+// Tries the fallback actions in order until one of them succeeds.
+async fn trigger_fallback(
+    name: &str,
+    fallback: &[std::sync::Arc<dyn Action>],
+    mut placeholders: PlaceholderMap,
+    error: Error,
+) -> Result<()> {
+    if fallback.is_empty() {
+        return Err(error);
+    }
+    log_ext::warn!("Action '{name}' failed, triggering fallback action(s): {error}");
+    placeholders.insert(String::from("failed_action"), String::from(name));
+    placeholders.insert(String::from("failed_error"), error.to_string());
+    for action in fallback.iter() {
+        match action.trigger(placeholders.clone()).await {
+            Ok(()) => return Ok(()),
+            Err(err) => log_ext::error!("Fallback action of action '{name}' failed: {err}"),
+        }
+    }
+    Err(Error(format!(
+        "Action '{name}' and all of its fallback actions failed: {error}"
+    )))
+}
+
+// Passes the triggers on to another action.
+struct Forward(std::sync::Arc<dyn Action>);
+
+#[async_trait]
+impl Action for Forward {
+    async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
+        self.0.trigger(placeholders).await
+    }
+}
+
 struct DisabledAction {}
 
 #[async_trait]
@@ -108,6 +430,12 @@ impl Action for DisabledAction {
                 placeholders.get("action_name").unwrap(),
                 placeholders.get("event_name").unwrap()
             );
+        } else if placeholders.contains_key("composite_name") {
+            log_ext::debug!(
+                "Disabled action '{}' triggered for composite alarm '{}'.",
+                placeholders.get("action_name").unwrap(),
+                placeholders.get("composite_name").unwrap()
+            );
         } else {
             log_ext::debug!(
                 "Disabled action '{}' triggered for alarm '{}', id '{}' from check '{}'.",
@@ -121,15 +449,64 @@ impl Action for DisabledAction {
     }
 }
 
-pub fn from_action_config(action_config: &config::Action) -> Result<std::sync::Arc<dyn Action>> {
-    macro_rules! create_action {
-        ($action:expr) => {
-            Ok(std::sync::Arc::new(ActionBase::new(
+pub fn from_action_config(
+    action_config: &config::Action,
+    actions: &ActionMap,
+    outbox: Option<&std::sync::Arc<Outbox>>,
+) -> Result<std::sync::Arc<dyn Action>> {
+    let outbox = if action_config.outbox {
+        Some(
+            outbox
+                .ok_or_else(|| {
+                    Error(String::from(
+                        "'outbox' needs the outbox to be configured in the general section.",
+                    ))
+                })?
+                .clone(),
+        )
+    } else {
+        None
+    };
+    // `throttle` enables the rate limit and deduplication, `deliver` the retries, fallback and
+    // outbox of the action.
+    macro_rules! action_base {
+        ($action:expr, $throttle:expr, $deliver:expr) => {{
+            let outbox = if $deliver { outbox.clone() } else { None };
+            let action = std::sync::Arc::new(ActionBase::new(
                 action_config.name.clone(),
                 std::time::Duration::from_secs(action_config.timeout as u64),
                 action_config.placeholders.clone(),
+                match &action_config.retry {
+                    Some(retry) if $deliver => Some(RetryPolicy::try_from(retry)?),
+                    _ => None,
+                },
+                if $deliver {
+                    action_config
+                        .fallback
+                        .iter()
+                        .map(|x| get_action(x, actions))
+                        .collect::<Result<_>>()?
+                } else {
+                    Vec::new()
+                },
+                if $throttle {
+                    throttle::from_action_config(action_config)?
+                } else {
+                    None
+                },
+                outbox.clone(),
                 $action,
-            )?) as std::sync::Arc<dyn Action>)
+            )?);
+            if let Some(outbox) = &outbox {
+                let replay: std::sync::Arc<dyn Replay> = action.clone();
+                outbox.register(&action_config.name, std::sync::Arc::downgrade(&replay));
+            }
+            action
+        }};
+    }
+    macro_rules! create_action {
+        ($action:expr) => {
+            Ok(action_base!($action, true, true) as std::sync::Arc<dyn Action>)
         };
     }
 
@@ -138,11 +515,59 @@ pub fn from_action_config(action_config: &config::Action) -> Result<std::sync::A
         create_action!(DisabledAction {})
     } else {
         match &action_config.type_ {
+            config::ActionType::ActionGroup(action_group) => {
+                // the members retry and fall back on their own, repeating the whole group would
+                // trigger the members that succeeded again
+                if action_config.retry.is_some()
+                    || !action_config.fallback.is_empty()
+                    || action_config.outbox
+                {
+                    return Err(Error(String::from(
+                        "'retry', 'fallback' and 'outbox' are not supported for ActionGroup, set them for its actions instead.",
+                    )));
+                }
+                Ok(action_base!(
+                    ActionGroup::new(
+                        action_group
+                            .actions
+                            .iter()
+                            .map(|x| Ok((x.clone(), get_action(x, actions)?)))
+                            .collect::<Result<_>>()?
+                    )?,
+                    true,
+                    false
+                ) as std::sync::Arc<dyn Action>)
+            }
+            config::ActionType::Digest(digest) => {
+                // retries, fallback and outbox apply to the digest when it is sent, not to the
+                // triggers that are collected
+                let sender =
+                    action_base!(Forward(get_action(&digest.action, actions)?), false, true);
+                Ok(action_base!(
+                    Digest::new(
+                        sender,
+                        std::time::Duration::from_secs(digest.window.into()),
+                        digest.item_template.clone(),
+                        digest.separator.clone(),
+                        digest.send_first,
+                    )?,
+                    true,
+                    false
+                ) as std::sync::Arc<dyn Action>)
+            }
             #[cfg(feature = "smtp")]
             config::ActionType::Email(_) => create_action!(Email::try_from(action_config)?),
             config::ActionType::Log(_) => create_action!(Log::try_from(action_config)?),
+            #[cfg(feature = "mqtt")]
+            config::ActionType::Mqtt(_) => create_action!(Mqtt::try_from(action_config)?),
             config::ActionType::Process(_) => create_action!(Process::try_from(action_config)?),
             #[cfg(feature = "http")]
+            config::ActionType::Gotify(_)
+            | config::ActionType::Matrix(_)
+            | config::ActionType::Ntfy(_)
+            | config::ActionType::Slack(_)
+            | config::ActionType::Telegram(_) => create_action!(Chat::try_from(action_config)?),
+            #[cfg(feature = "http")]
             config::ActionType::Webhook(_) => create_action!(Webhook::try_from(action_config)?),
         }
     }
@@ -164,22 +589,37 @@ mod test {
     use super::*;
     use mockall::predicate::*;
 
+    // Removes the random `trigger_id` after checking that it is set.
+    fn without_trigger_id(placeholders: &PlaceholderMap) -> PlaceholderMap {
+        let mut placeholders = placeholders.clone();
+        assert_eq!(placeholders.remove("trigger_id").unwrap().len(), 32);
+        placeholders
+    }
+
     #[tokio::test]
     async fn test_placeholders() {
         let mut mock_action = MockAction::new();
         mock_action
             .expect_trigger()
             .once()
-            .with(eq(PlaceholderMap::from([
-                (String::from("action_name"), String::from("Name")),
-                (String::from("Hello"), String::from("World")),
-                (String::from("Foo"), String::from("Bar")),
-            ])))
+            .with(function(|placeholders: &PlaceholderMap| {
+                without_trigger_id(placeholders)
+                    == PlaceholderMap::from([
+                        (String::from("action_name"), String::from("Name")),
+                        (String::from("action_attempt"), String::from("1")),
+                        (String::from("Hello"), String::from("World")),
+                        (String::from("Foo"), String::from("Bar")),
+                    ])
+            }))
             .returning(|_| Ok(()));
         let action = ActionBase::new(
             String::from("Name"),
             std::time::Duration::from_secs(1),
             PlaceholderMap::from([(String::from("Hello"), String::from("World"))]),
+            None,
+            vec![],
+            None,
+            None,
             mock_action,
         )
         .unwrap();
@@ -206,9 +646,178 @@ mod test {
             String::from("Name"),
             std::time::Duration::from_secs(1),
             PlaceholderMap::new(),
+            None,
+            vec![],
+            None,
+            None,
             TimeoutMockAction {},
         )
         .unwrap();
         assert!(action.trigger(PlaceholderMap::new()).await.is_err());
     }
+
+    #[test]
+    fn test_retry_delay() {
+        let seconds = std::time::Duration::from_secs;
+        assert!(RetryPolicy::new(0, seconds(1), 2.0, seconds(10)).is_err());
+        assert!(RetryPolicy::new(3, seconds(1), 0.5, seconds(10)).is_err());
+        assert!(RetryPolicy::new(3, seconds(1), f64::NAN, seconds(10)).is_err());
+        assert!(RetryPolicy::new(3, seconds(10), 2.0, seconds(1)).is_err());
+        let retry = RetryPolicy::new(5, seconds(1), 2.0, seconds(10)).unwrap();
+        assert_eq!(retry.delay(1), seconds(1));
+        assert_eq!(retry.delay(2), seconds(2));
+        assert_eq!(retry.delay(4), seconds(8));
+        assert_eq!(retry.delay(5), seconds(10));
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let mut mock_action = MockAction::new();
+        let mut seq = mockall::Sequence::new();
+        mock_action
+            .expect_trigger()
+            .times(2)
+            .in_sequence(&mut seq)
+            .returning(|_| Err(Error(String::from("Unreachable"))));
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(function(|placeholders: &PlaceholderMap| {
+                placeholders.get("action_attempt").unwrap() == "3"
+            }))
+            .returning(|_| Ok(()));
+        let millis = std::time::Duration::from_millis;
+        let action = ActionBase::new(
+            String::from("Name"),
+            std::time::Duration::from_secs(1),
+            PlaceholderMap::new(),
+            Some(RetryPolicy::new(3, millis(10), 2.0, millis(100)).unwrap()),
+            vec![],
+            None,
+            None,
+            mock_action,
+        )
+        .unwrap();
+        action
+            .trigger(PlaceholderMap::from([(
+                String::from("event_name"),
+                String::from("Event"),
+            )]))
+            .await
+            .unwrap();
+        tokio::time::sleep(millis(200)).await;
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let mut mock_action = MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .returning(|_| Err(Error(String::from("Unreachable"))));
+        let mut mock_failing_fallback = MockAction::new();
+        mock_failing_fallback
+            .expect_trigger()
+            .once()
+            .returning(|_| Err(Error(String::from("Disk full"))));
+        let mut mock_fallback = MockAction::new();
+        mock_fallback
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                without_trigger_id(placeholders)
+                    == PlaceholderMap::from([
+                        (String::from("Foo"), String::from("Bar")),
+                        (String::from("failed_action"), String::from("Name")),
+                        (String::from("failed_error"), String::from("Unreachable")),
+                    ])
+            }))
+            .returning(|_| Ok(()));
+        let action = ActionBase::new(
+            String::from("Name"),
+            std::time::Duration::from_secs(1),
+            PlaceholderMap::from([(String::from("Hello"), String::from("World"))]),
+            None,
+            vec![
+                std::sync::Arc::new(mock_failing_fallback),
+                std::sync::Arc::new(mock_fallback),
+            ],
+            None,
+            None,
+            mock_action,
+        )
+        .unwrap();
+        action
+            .trigger(PlaceholderMap::from([(
+                String::from("Foo"),
+                String::from("Bar"),
+            )]))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let path = std::env::temp_dir().join(format!("minmon-action-{}", std::process::id()));
+        let outbox = std::sync::Arc::new(
+            Outbox::new(
+                path.clone(),
+                std::time::Duration::from_secs(60),
+                std::time::Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        let mut mock_action = MockAction::new();
+        let mut seq = mockall::Sequence::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .returning(|_| Err(Error(String::from("Unreachable"))));
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(function(|placeholders: &PlaceholderMap| {
+                placeholders.get("Foo").unwrap() == "2"
+            }))
+            .returning(|_| Ok(()));
+        mock_action
+            .expect_trigger()
+            .once()
+            .in_sequence(&mut seq)
+            .with(function(|placeholders: &PlaceholderMap| {
+                placeholders.get("Foo").unwrap() == "1"
+            }))
+            .returning(|_| Ok(()));
+        let action = std::sync::Arc::new(
+            ActionBase::new(
+                String::from("Name"),
+                std::time::Duration::from_secs(1),
+                PlaceholderMap::new(),
+                None,
+                vec![],
+                None,
+                Some(outbox.clone()),
+                mock_action,
+            )
+            .unwrap(),
+        );
+        let replay: std::sync::Arc<dyn Replay> = action.clone();
+        outbox.register("Name", std::sync::Arc::downgrade(&replay));
+        let placeholders = |x: &str| {
+            PlaceholderMap::from([
+                (String::from("event_name"), String::from("Event")),
+                (String::from("Foo"), String::from(x)),
+            ])
+        };
+        assert!(action.trigger(placeholders("1")).await.is_err());
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 1);
+        // the successful trigger also replays the failed one
+        assert!(action.trigger(placeholders("2")).await.is_ok());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 0);
+        std::fs::remove_dir(&path).unwrap();
+    }
 }
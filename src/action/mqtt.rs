@@ -0,0 +1,634 @@
+use async_trait::async_trait;
+use rumqttc::{
+    AsyncClient, ConnectionError, Event, EventLoop, LastWill, MqttOptions, Outgoing, Packet, QoS,
+};
+
+use super::Action;
+use crate::config;
+use crate::template::Template;
+use crate::{Error, PlaceholderMap, Result};
+
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+const KEEP_ALIVE: std::time::Duration = std::time::Duration::from_secs(30);
+const REQUEST_CAPACITY: usize = 16;
+
+// Topic, payload, QoS and retain flag of the message that is published when connected.
+type Online = (String, String, QoS, bool);
+
+// State of the connection to the broker: `None` until the first connection attempt finished.
+type Connected = tokio::sync::watch::Receiver<Option<bool>>;
+
+pub struct Mqtt {
+    name: String,
+    topic: Template,
+    payload: Template,
+    qos: QoS,
+    retain: bool,
+    options: MqttOptions,
+    online: Option<Online>,
+    // connects on the first trigger, so actions that are never used do not connect
+    connection: std::sync::OnceLock<(Publisher, Connected)>,
+}
+
+// Waiters for the acknowledgements (PUBACK or PUBCOMP) of the published messages.
+#[derive(Default)]
+struct Acks {
+    // one entry per message in the request queue of the client, in the same order, `None` for
+    // messages nobody waits for
+    queued: std::collections::VecDeque<Option<tokio::sync::oneshot::Sender<()>>>,
+    // by packet ID, once the event loop assigned one
+    inflight: std::collections::HashMap<u16, tokio::sync::oneshot::Sender<()>>,
+}
+
+#[derive(Clone)]
+struct Publisher {
+    client: AsyncClient,
+    acks: std::sync::Arc<std::sync::Mutex<Acks>>,
+}
+
+impl Publisher {
+    // Queues the message. For QoS 1 and 2, the returned receiver completes when the broker
+    // acknowledged the message.
+    fn publish(
+        &self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: String,
+    ) -> Result<Option<tokio::sync::oneshot::Receiver<()>>> {
+        let (sender, receiver) = match qos {
+            QoS::AtMostOnce => (None, None),
+            _ => {
+                let (sender, receiver) = tokio::sync::oneshot::channel();
+                (Some(sender), Some(receiver))
+            }
+        };
+        // the lock keeps the order of the waiters and of the queued requests the same
+        let mut acks = self.acks.lock().unwrap();
+        acks.queued.push_back(sender);
+        if let Err(err) = self.client.try_publish(topic, qos, retain, payload) {
+            acks.queued.pop_back();
+            return Err(Error(format!("Failed to publish MQTT message: {err}")));
+        }
+        Ok(receiver)
+    }
+
+    // The event loop reports each queued message once when it assigns the packet ID. Messages
+    // that are resent after a reconnect keep their packet ID.
+    fn sent(&self, pkid: u16) {
+        let mut acks = self.acks.lock().unwrap();
+        if pkid != 0 && acks.inflight.contains_key(&pkid) {
+            return;
+        }
+        if let Some(Some(sender)) = acks.queued.pop_front() {
+            acks.inflight.insert(pkid, sender);
+        }
+    }
+
+    fn acknowledged(&self, pkid: u16) {
+        if let Some(sender) = self.acks.lock().unwrap().inflight.remove(&pkid) {
+            // the trigger might have timed out already
+            let _ = sender.send(());
+        }
+    }
+}
+
+fn qos(qos: u8) -> Result<QoS> {
+    match qos {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(Error(String::from("'qos' must be 0, 1 or 2."))),
+    }
+}
+
+#[cfg(feature = "__tls")]
+fn transport(ca_file: &Option<String>) -> Result<rumqttc::Transport> {
+    use rumqttc::TlsConfiguration;
+    Ok(rumqttc::Transport::tls_with_config(match ca_file {
+        Some(path) => TlsConfiguration::SimpleNative {
+            ca: super::read_file(path)?,
+            client_auth: None,
+        },
+        None => TlsConfiguration::Native,
+    }))
+}
+
+#[cfg(all(feature = "__rustls", not(feature = "__tls")))]
+fn transport(ca_file: &Option<String>) -> Result<rumqttc::Transport> {
+    use rumqttc::TlsConfiguration;
+    Ok(rumqttc::Transport::tls_with_config(match ca_file {
+        Some(path) => TlsConfiguration::Simple {
+            ca: super::read_file(path)?,
+            alpn: None,
+            client_auth: None,
+        },
+        None => TlsConfiguration::default(),
+    }))
+}
+
+#[cfg(not(any(feature = "__tls", feature = "__rustls")))]
+fn transport(_ca_file: &Option<String>) -> Result<rumqttc::Transport> {
+    Err(Error(String::from(
+        "TLS is not available because MinMon was built without TLS support.",
+    )))
+}
+
+fn options(name: &str, mqtt: &config::ActionMqtt) -> Result<MqttOptions> {
+    let client_id = match &mqtt.client_id {
+        Some(client_id) if client_id.is_empty() => {
+            return Err(Error(String::from("'client_id' cannot be empty.")))
+        }
+        Some(client_id) => client_id.clone(),
+        // each action has its own connection, so the name keeps them apart
+        None => format!("minmon-{}-{name}", std::process::id()),
+    };
+    let port = mqtt.port.unwrap_or(if mqtt.tls { 8883 } else { 1883 });
+    let mut options = MqttOptions::new(client_id, &mqtt.broker, port);
+    options.set_keep_alive(KEEP_ALIVE);
+    if mqtt.tls {
+        options.set_transport(transport(&mqtt.ca_file)?);
+    } else if mqtt.ca_file.is_some() {
+        return Err(Error(String::from(
+            "'ca_file' requires 'tls' to be enabled.",
+        )));
+    }
+    match (&mqtt.username, &mqtt.password) {
+        (Some(username), Some(password)) => {
+            if username.is_empty() {
+                return Err(Error(String::from("'username' cannot be empty.")));
+            }
+            options.set_credentials(username, password);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error(String::from(
+                "'username' and 'password' must be set together.",
+            )))
+        }
+    }
+    if let Some(last_will) = &mqtt.last_will {
+        if last_will.topic.is_empty() {
+            return Err(Error(String::from("'last_will.topic' cannot be empty.")));
+        }
+        options.set_last_will(LastWill::new(
+            &last_will.topic,
+            last_will.payload.as_str(),
+            qos(last_will.qos)?,
+            last_will.retain,
+        ));
+    }
+    log::debug!("Action '{name}' uses MQTT broker {}:{port}.", mqtt.broker);
+    Ok(options)
+}
+
+// Drives the connection to the broker. The event loop reconnects by itself when it is polled
+// again after an error.
+fn run_event_loop(
+    name: String,
+    mut event_loop: EventLoop,
+    publisher: Publisher,
+    connected: tokio::sync::watch::Sender<Option<bool>>,
+    online: Option<Online>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    log::info!("Action '{name}' connected to the MQTT broker.");
+                    if let Some((topic, payload, qos, retain)) = &online {
+                        if let Err(err) = publisher.publish(topic, *qos, *retain, payload.clone()) {
+                            log::error!(
+                                "Action '{name}' could not publish the online payload: {err}"
+                            );
+                        }
+                    }
+                    // after the online payload so it is published first
+                    connected.send_replace(Some(true));
+                }
+                Ok(Event::Outgoing(Outgoing::Publish(pkid))) => publisher.sent(pkid),
+                Ok(Event::Incoming(Packet::PubAck(ack))) => publisher.acknowledged(ack.pkid),
+                Ok(Event::Incoming(Packet::PubComp(comp))) => publisher.acknowledged(comp.pkid),
+                Ok(_) => {}
+                Err(ConnectionError::RequestsDone) => break,
+                Err(err) => {
+                    if connected.send_replace(Some(false)) == Some(true) {
+                        log::error!(
+                            "Action '{name}' lost the connection to the MQTT broker: {err}"
+                        );
+                    } else {
+                        log::warn!("Action '{name}' could not connect to the MQTT broker: {err}");
+                    }
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+}
+
+impl TryFrom<&config::Action> for Mqtt {
+    type Error = Error;
+
+    fn try_from(action: &config::Action) -> std::result::Result<Self, Self::Error> {
+        if let config::ActionType::Mqtt(mqtt) = &action.type_ {
+            if mqtt.broker.is_empty() {
+                Err(Error(String::from("'broker' cannot be empty.")))
+            } else if mqtt.topic.is_empty() {
+                Err(Error(String::from("'topic' cannot be empty.")))
+            } else {
+                let topic =
+                    Template::new(&mqtt.topic, action.template_syntax, config::Escape::None)
+                        .map_err(|x| Error(format!("Invalid 'topic': {x}")))?;
+                let payload =
+                    Template::new(&mqtt.payload, action.template_syntax, config::Escape::None)
+                        .map_err(|x| Error(format!("Invalid 'payload': {x}")))?;
+                let qos = qos(mqtt.qos)?;
+                let online = match &mqtt.last_will {
+                    Some(last_will) => match &last_will.online_payload {
+                        Some(online_payload) => Some((
+                            last_will.topic.clone(),
+                            online_payload.clone(),
+                            self::qos(last_will.qos)?,
+                            last_will.retain,
+                        )),
+                        None => None,
+                    },
+                    None => None,
+                };
+                Ok(Self {
+                    name: action.name.clone(),
+                    topic,
+                    payload,
+                    qos,
+                    retain: mqtt.retain,
+                    options: options(&action.name, mqtt)?,
+                    online,
+                    connection: std::sync::OnceLock::new(),
+                })
+            }
+        } else {
+            panic!();
+        }
+    }
+}
+
+impl Mqtt {
+    fn connection(&self) -> &(Publisher, Connected) {
+        self.connection.get_or_init(|| {
+            let (client, event_loop) = AsyncClient::new(self.options.clone(), REQUEST_CAPACITY);
+            let publisher = Publisher {
+                client,
+                acks: std::sync::Arc::new(std::sync::Mutex::new(Acks::default())),
+            };
+            let (sender, receiver) = tokio::sync::watch::channel(None);
+            run_event_loop(
+                self.name.clone(),
+                event_loop,
+                publisher.clone(),
+                sender,
+                self.online.clone(),
+            );
+            (publisher, receiver)
+        })
+    }
+}
+
+#[async_trait]
+impl Action for Mqtt {
+    // The Last Will is registered with the connection, so it must not wait for the first trigger.
+    fn start(&self) -> Result<()> {
+        if self.options.last_will().is_some() {
+            self.connection();
+        }
+        Ok(())
+    }
+
+    async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
+        let (publisher, connected) = self.connection();
+        // waits for the first connection attempt
+        let connected = matches!(
+            connected.clone().wait_for(|x| x.is_some()).await.as_deref(),
+            Ok(Some(true))
+        );
+        if !connected {
+            return Err(Error(String::from("Not connected to the MQTT broker.")));
+        }
+        let topic = self.topic.fill(&placeholders);
+        let payload = self.payload.fill(&placeholders);
+        match publisher.publish(&topic, self.qos, self.retain, payload)? {
+            Some(acknowledged) => acknowledged.await.map_err(|_| {
+                Error(String::from(
+                    "The MQTT message was not acknowledged by the broker.",
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[derive(Debug, PartialEq)]
+    struct Publish {
+        topic: String,
+        payload: String,
+        qos: u8,
+        retain: bool,
+    }
+
+    async fn read_packet(stream: &mut tokio::net::TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = stream.read_u8().await.ok()?;
+        let mut length = 0;
+        for shift in (0..28).step_by(7) {
+            let byte = stream.read_u8().await.ok()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    // Minimal MQTT 3.1.1 broker that accepts a single client. Returns its port, the body of the
+    // CONNECT packet and the received messages. QoS 1 and 2 messages are only acknowledged if
+    // `acknowledge` is `true`.
+    async fn broker(
+        acknowledge: bool,
+    ) -> (
+        u16,
+        Arc<std::sync::Mutex<Vec<u8>>>,
+        Arc<std::sync::Mutex<Vec<Publish>>>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connect = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let messages = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (connect_clone, messages_clone) = (connect.clone(), messages.clone());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Some((header, body)) = read_packet(&mut stream).await {
+                match header >> 4 {
+                    // CONNECT
+                    1 => {
+                        *connect_clone.lock().unwrap() = body;
+                        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+                    }
+                    // PUBLISH
+                    3 => {
+                        let qos = (header >> 1) & 0x03;
+                        let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap();
+                        let mut offset = 2 + topic_length;
+                        if qos > 0 && acknowledge {
+                            let packet_id = &body[offset..offset + 2];
+                            // PUBACK or PUBREC
+                            let response = if qos == 1 { 0x40 } else { 0x50 };
+                            stream
+                                .write_all(&[response, 0x02, packet_id[0], packet_id[1]])
+                                .await
+                                .unwrap();
+                        }
+                        if qos > 0 {
+                            offset += 2;
+                        }
+                        messages_clone.lock().unwrap().push(Publish {
+                            topic,
+                            payload: String::from_utf8(body[offset..].to_vec()).unwrap(),
+                            qos,
+                            retain: header & 0x01 == 1,
+                        });
+                    }
+                    // PUBREL
+                    6 => stream
+                        .write_all(&[0x70, 0x02, body[0], body[1]])
+                        .await
+                        .unwrap(),
+                    // PINGREQ
+                    12 => stream.write_all(&[0xd0, 0x00]).await.unwrap(),
+                    _ => {}
+                }
+            }
+        });
+        (port, connect, messages)
+    }
+
+    fn mqtt(options: &str) -> Result<Mqtt> {
+        let config = config::Config::try_from(
+            format!(
+                r#"
+                [[actions]]
+                name = "MQTT"
+                type = "Mqtt"
+                broker = "127.0.0.1"
+                {options}
+                "#
+            )
+            .as_str(),
+        )
+        .unwrap();
+        Mqtt::try_from(config.actions.first().unwrap())
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("Timed out.");
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let (port, connect, messages) = broker(true).await;
+        let mqtt = mqtt(&format!(
+            r#"
+            port = {port}
+            client_id = "minmon-test"
+            topic = "minmon/{{{{check_name}}}}"
+            payload = "{{{{alarm_state}}}}"
+            qos = 1
+            retain = true
+            username = "user"
+            password = "secret"
+            last_will = {{ topic = "minmon/status", payload = "offline", online_payload = "online", retain = true }}
+            "#
+        ))
+        .unwrap();
+        // the connection is only established by the first trigger
+        assert!(connect.lock().unwrap().is_empty());
+        let placeholders = PlaceholderMap::from([
+            (String::from("check_name"), String::from("Disk")),
+            (String::from("alarm_state"), String::from("Bad")),
+        ]);
+        mqtt.trigger(placeholders).await.unwrap();
+        wait_until(|| messages.lock().unwrap().len() == 2).await;
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec![
+                Publish {
+                    topic: String::from("minmon/status"),
+                    payload: String::from("online"),
+                    qos: 0,
+                    retain: true,
+                },
+                Publish {
+                    topic: String::from("minmon/Disk"),
+                    payload: String::from("Bad"),
+                    qos: 1,
+                    retain: true,
+                },
+            ]
+        );
+        let connect = String::from_utf8_lossy(&connect.lock().unwrap()).into_owned();
+        for field in ["minmon-test", "minmon/status", "offline", "user", "secret"] {
+            assert!(connect.contains(field));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_last_will() {
+        let (port, connect, messages) = broker(true).await;
+        let mqtt = mqtt(&format!(
+            r#"
+            port = {port}
+            topic = "minmon"
+            last_will = {{ topic = "minmon/status", payload = "offline", online_payload = "online" }}
+            "#
+        ))
+        .unwrap();
+        // connects at startup without any trigger
+        mqtt.start().unwrap();
+        wait_until(|| messages.lock().unwrap().len() == 1).await;
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec![Publish {
+                topic: String::from("minmon/status"),
+                payload: String::from("online"),
+                qos: 0,
+                retain: false,
+            }]
+        );
+        assert!(String::from_utf8_lossy(&connect.lock().unwrap()).contains("offline"));
+    }
+
+    #[tokio::test]
+    async fn test_exactly_once() {
+        let (port, _, messages) = broker(true).await;
+        let mqtt = mqtt(&format!(
+            r#"
+            port = {port}
+            topic = "minmon"
+            qos = 2
+            "#
+        ))
+        .unwrap();
+        // returns after PUBREC, PUBREL and PUBCOMP
+        for _ in 0..3 {
+            mqtt.trigger(PlaceholderMap::new()).await.unwrap();
+        }
+        assert_eq!(messages.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_not_acknowledged() {
+        let (port, _, messages) = broker(false).await;
+        let mqtt = mqtt(&format!(
+            r#"
+            port = {port}
+            topic = "minmon"
+            qos = 2
+            "#
+        ))
+        .unwrap();
+        let millis = std::time::Duration::from_millis;
+        assert!(
+            tokio::time::timeout(millis(200), mqtt.trigger(PlaceholderMap::new()))
+                .await
+                .is_err()
+        );
+        assert_eq!(messages.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_not_connected() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let mqtt = mqtt(&format!(
+            r#"
+            port = {port}
+            topic = "minmon"
+            "#
+        ))
+        .unwrap();
+        assert!(mqtt.trigger(PlaceholderMap::new()).await.is_err());
+    }
+
+    #[test]
+    fn test_client_id() {
+        let client_id = |options: &str| {
+            let config = config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "MQTT"
+                    type = "Mqtt"
+                    broker = "127.0.0.1"
+                    topic = "minmon"
+                    {options}
+                    "#
+                )
+                .as_str(),
+            )
+            .unwrap();
+            match &config.actions.first().unwrap().type_ {
+                config::ActionType::Mqtt(mqtt) => super::options("MQTT", mqtt).unwrap().client_id(),
+                _ => panic!(),
+            }
+        };
+        assert_eq!(client_id(""), format!("minmon-{}-MQTT", std::process::id()));
+        assert_eq!(client_id(r#"client_id = "foo""#), "foo");
+    }
+
+    #[tokio::test]
+    async fn test_validation() {
+        assert!(mqtt(r#"topic = "minmon""#).is_ok());
+        assert!(mqtt(r#"topic = """#).is_err());
+        assert!(mqtt(
+            r#"
+            topic = "minmon"
+            qos = 3
+            "#
+        )
+        .is_err());
+        assert!(mqtt(
+            r#"
+            topic = "minmon"
+            username = "user"
+            "#
+        )
+        .is_err());
+        assert!(mqtt(
+            r#"
+            topic = "minmon"
+            ca_file = "/etc/ssl/ca.pem"
+            "#
+        )
+        .is_err());
+        assert!(mqtt(
+            r#"
+            topic = "minmon"
+            last_will = { topic = "", payload = "offline" }
+            "#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::config;
+use crate::{Error, PlaceholderMap, Result};
+
+struct RateLimit {
+    count: u32,
+    period: std::time::Duration,
+}
+
+struct Dedup {
+    key: String,
+    period: std::time::Duration,
+}
+
+struct ThrottleState {
+    tokens: f64,
+    last_refill: std::time::Instant,
+    // time and alarm state each deduplication key was last let through with
+    keys: std::collections::HashMap<String, (std::time::Instant, Option<String>)>,
+    suppressed: u32,
+}
+
+// Rate limit (token bucket) and deduplication of the triggers of an action.
+pub struct Throttle {
+    rate_limit: Option<RateLimit>,
+    dedup: Option<Dedup>,
+    state: std::sync::Mutex<ThrottleState>,
+}
+
+impl Throttle {
+    fn new(rate_limit: Option<RateLimit>, dedup: Option<Dedup>) -> Result<Self> {
+        if let Some(rate_limit) = &rate_limit {
+            if rate_limit.count == 0 {
+                return Err(Error(String::from("'count' cannot be 0.")));
+            }
+            if rate_limit.period.is_zero() {
+                return Err(Error(String::from("'period' cannot be 0.")));
+            }
+        }
+        if let Some(dedup) = &dedup {
+            if dedup.key.is_empty() {
+                return Err(Error(String::from("'key' cannot be empty.")));
+            }
+            if dedup.period.is_zero() {
+                return Err(Error(String::from("'period' cannot be 0.")));
+            }
+        }
+        Ok(Self {
+            state: std::sync::Mutex::new(ThrottleState {
+                tokens: rate_limit.as_ref().map(|x| x.count).unwrap_or_default() as f64,
+                last_refill: std::time::Instant::now(),
+                keys: std::collections::HashMap::new(),
+                suppressed: 0,
+            }),
+            rate_limit,
+            dedup,
+        })
+    }
+
+    // Returns the number of triggers that were suppressed since the last one that was let
+    // through, or the reason why this one is suppressed.
+    pub fn admit(&self, placeholders: &PlaceholderMap) -> std::result::Result<u32, &'static str> {
+        self.admit_at(placeholders, std::time::Instant::now())
+    }
+
+    fn admit_at(
+        &self,
+        placeholders: &PlaceholderMap,
+        now: std::time::Instant,
+    ) -> std::result::Result<u32, &'static str> {
+        let mut state = self.state.lock().unwrap();
+        let alarm_state = placeholders.get("alarm_state").cloned();
+        let key = self.dedup.as_ref().map(|dedup| {
+            state
+                .keys
+                .retain(|_, (instant, _)| now.duration_since(*instant) < dedup.period);
+            crate::fill_placeholders(&dedup.key, placeholders)
+        });
+        // a change of the alarm state (e.g. a recovery) is never a duplicate
+        if key
+            .as_ref()
+            .and_then(|x| state.keys.get(x))
+            .is_some_and(|(_, last_state)| *last_state == alarm_state)
+        {
+            state.suppressed += 1;
+            return Err("duplicate");
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+            let count = rate_limit.count as f64;
+            state.tokens =
+                (state.tokens + elapsed * count / rate_limit.period.as_secs_f64()).min(count);
+            state.last_refill = now;
+            if state.tokens < 1.0 {
+                state.suppressed += 1;
+                return Err("rate limit");
+            }
+            state.tokens -= 1.0;
+        }
+        if let Some(key) = key {
+            state.keys.insert(key, (now, alarm_state));
+        }
+        Ok(std::mem::take(&mut state.suppressed))
+    }
+}
+
+pub fn from_action_config(action: &config::Action) -> Result<Option<Throttle>> {
+    if action.rate_limit.is_none() && action.dedup.is_none() {
+        return Ok(None);
+    }
+    Throttle::new(
+        action.rate_limit.as_ref().map(|x| RateLimit {
+            count: x.count,
+            period: std::time::Duration::from_secs(x.period.into()),
+        }),
+        action.dedup.as_ref().map(|x| Dedup {
+            key: x.key.clone(),
+            period: std::time::Duration::from_secs(x.period.into()),
+        }),
+    )
+    .map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        let seconds = std::time::Duration::from_secs;
+        assert!(Throttle::new(
+            Some(RateLimit {
+                count: 0,
+                period: seconds(60)
+            }),
+            None
+        )
+        .is_err());
+        assert!(Throttle::new(
+            None,
+            Some(Dedup {
+                key: String::new(),
+                period: seconds(60)
+            })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let throttle = Throttle::new(
+            Some(RateLimit {
+                count: 2,
+                period: std::time::Duration::from_secs(60),
+            }),
+            None,
+        )
+        .unwrap();
+        let start = std::time::Instant::now();
+        let at = |x| start + std::time::Duration::from_secs(x);
+        let placeholders = PlaceholderMap::new();
+        assert_eq!(throttle.admit_at(&placeholders, at(0)), Ok(0));
+        assert_eq!(throttle.admit_at(&placeholders, at(1)), Ok(0));
+        assert!(throttle.admit_at(&placeholders, at(2)).is_err());
+        assert!(throttle.admit_at(&placeholders, at(3)).is_err());
+        // one token per 30 seconds
+        assert_eq!(throttle.admit_at(&placeholders, at(32)), Ok(2));
+        assert!(throttle.admit_at(&placeholders, at(33)).is_err());
+    }
+
+    #[test]
+    fn test_dedup() {
+        let throttle = Throttle::new(
+            None,
+            Some(Dedup {
+                key: String::from("{{check_name}}/{{check_id}}"),
+                period: std::time::Duration::from_secs(60),
+            }),
+        )
+        .unwrap();
+        let start = std::time::Instant::now();
+        let at = |x| start + std::time::Duration::from_secs(x);
+        let placeholders = |x: &str| {
+            PlaceholderMap::from([
+                (String::from("check_name"), String::from("Check")),
+                (String::from("check_id"), String::from(x)),
+            ])
+        };
+        assert_eq!(throttle.admit_at(&placeholders("a"), at(0)), Ok(0));
+        assert!(throttle.admit_at(&placeholders("a"), at(1)).is_err());
+        assert_eq!(throttle.admit_at(&placeholders("b"), at(2)), Ok(1));
+        assert!(throttle.admit_at(&placeholders("b"), at(61)).is_err());
+        assert_eq!(throttle.admit_at(&placeholders("a"), at(61)), Ok(1));
+        // state changes are let through
+        let with_state = |x: &str| {
+            let mut placeholders = placeholders("a");
+            placeholders.insert(String::from("alarm_state"), String::from(x));
+            placeholders
+        };
+        assert_eq!(throttle.admit_at(&with_state("Bad"), at(62)), Ok(0));
+        assert!(throttle.admit_at(&with_state("Bad"), at(63)).is_err());
+        assert_eq!(throttle.admit_at(&with_state("Good"), at(64)), Ok(1));
+        assert_eq!(throttle.admit_at(&with_state("Bad"), at(65)), Ok(0));
+    }
+}
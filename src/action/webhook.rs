@@ -5,26 +5,215 @@ use reqwest::header::HeaderValue;
 
 use super::Action;
 use crate::config;
+use crate::template::Template;
 use crate::{Error, PlaceholderMap, Result};
 
+// Maximum number of characters of the response body in error messages.
+const RESPONSE_BODY_MAX: usize = 512;
+
+// Conditions for a response to count as success, besides a 2xx status code by default.
+#[derive(Default)]
+pub(super) struct ResponseCheck {
+    status: Vec<u16>,
+    regex: Option<regex::Regex>,
+    json: Option<(String, serde_json::Value)>,
+}
+
+impl ResponseCheck {
+    fn check(&self, status: reqwest::StatusCode, body: &str) -> std::result::Result<(), String> {
+        if self.status.is_empty() {
+            if !status.is_success() {
+                return Err(format!(
+                    "HTTP status code {} indicates error.",
+                    status.as_u16()
+                ));
+            }
+        } else if !self.status.contains(&status.as_u16()) {
+            return Err(format!(
+                "HTTP status code {} is not accepted.",
+                status.as_u16()
+            ));
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(body) {
+                return Err(String::from(
+                    "Response body does not match 'success_regex'.",
+                ));
+            }
+        }
+        if let Some((pointer, expected)) = &self.json {
+            let value: serde_json::Value = serde_json::from_str(body)
+                .map_err(|x| format!("Response body is not valid JSON: {x}"))?;
+            match value.pointer(pointer) {
+                Some(value) if value == expected => {}
+                Some(value) => {
+                    return Err(format!(
+                        "Response value at '{pointer}' is {value} instead of {expected}."
+                    ))
+                }
+                None => return Err(format!("Response has no value at '{pointer}'.")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&config::ActionWebhook> for ResponseCheck {
+    type Error = Error;
+
+    fn try_from(web_hook: &config::ActionWebhook) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            status: web_hook.success_status.clone(),
+            regex: match &web_hook.success_regex {
+                Some(regex) => Some(
+                    regex::Regex::new(regex)
+                        .map_err(|x| Error(format!("Invalid 'success_regex': {x}")))?,
+                ),
+                None => None,
+            },
+            json: match &web_hook.success_json {
+                Some(success_json) => Some((
+                    success_json.pointer.clone(),
+                    serde_json::to_value(&success_json.value)
+                        .map_err(|x| Error(format!("Invalid 'success_json' value: {x}")))?,
+                )),
+                None => None,
+            },
+        })
+    }
+}
+
+enum Auth {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
 pub struct Webhook {
-    url: String,
+    url: Template,
     method: reqwest::Method,
-    headers: reqwest::header::HeaderMap<String>,
-    body: String,
+    headers: reqwest::header::HeaderMap<Template>,
+    body: Template,
+    auth: Option<Auth>,
+    client: reqwest::Client,
+    response_check: ResponseCheck,
+}
+
+// Secrets are read from files so they do not need to be in the config file.
+fn read_secret(path: &str) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map(|x| String::from(x.trim()))
+        .map_err(|x| Error(format!("Could not read {path}: {x}")))
+}
+
+#[cfg(feature = "__tls")]
+fn identity(certificate: &[u8], key: &[u8]) -> Result<reqwest::Identity> {
+    reqwest::Identity::from_pkcs8_pem(certificate, key)
+        .map_err(|x| Error(format!("Invalid client certificate: {x}")))
+}
+
+#[cfg(all(feature = "__rustls", not(feature = "__tls")))]
+fn identity(certificate: &[u8], key: &[u8]) -> Result<reqwest::Identity> {
+    reqwest::Identity::from_pem(&[certificate, b"\n", key].concat())
+        .map_err(|x| Error(format!("Invalid client certificate: {x}")))
+}
+
+#[cfg(any(feature = "__tls", feature = "__rustls"))]
+fn tls(
+    mut builder: reqwest::ClientBuilder,
+    web_hook: &config::ActionWebhook,
+) -> Result<reqwest::ClientBuilder> {
+    if let Some(path) = &web_hook.ca_file {
+        let certificates = reqwest::Certificate::from_pem_bundle(&super::read_file(path)?)
+            .map_err(|x| Error(format!("Invalid CA certificates in {path}: {x}")))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    match (&web_hook.client_cert_file, &web_hook.client_key_file) {
+        (Some(certificate), Some(key)) => {
+            builder = builder.identity(identity(
+                &super::read_file(certificate)?,
+                &super::read_file(key)?,
+            )?);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error(String::from(
+                "'client_cert_file' and 'client_key_file' must be set together.",
+            )))
+        }
+    }
+    Ok(builder.danger_accept_invalid_certs(web_hook.insecure))
+}
+
+#[cfg(not(any(feature = "__tls", feature = "__rustls")))]
+fn tls(
+    _builder: reqwest::ClientBuilder,
+    _web_hook: &config::ActionWebhook,
+) -> Result<reqwest::ClientBuilder> {
+    Err(Error(String::from(
+        "TLS options are not available because MinMon was built without TLS support.",
+    )))
+}
+
+fn client(web_hook: &config::ActionWebhook) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = &web_hook.proxy {
+        builder = builder
+            .proxy(reqwest::Proxy::all(proxy).map_err(|x| Error(format!("Invalid 'proxy': {x}")))?);
+    }
+    if web_hook.ca_file.is_some()
+        || web_hook.client_cert_file.is_some()
+        || web_hook.client_key_file.is_some()
+        || web_hook.insecure
+    {
+        builder = tls(builder, web_hook)?;
+    }
+    builder
+        .build()
+        .map_err(|x| Error(format!("Could not create HTTP client: {x}")))
+}
+
+fn auth(web_hook: &config::ActionWebhook) -> Result<Option<Auth>> {
+    match (&web_hook.basic_auth, &web_hook.bearer_token_file) {
+        (Some(basic_auth), None) => {
+            let password = match (&basic_auth.password, &basic_auth.password_file) {
+                (Some(password), None) => password.clone(),
+                (None, Some(path)) => read_secret(path)?,
+                (None, None) => String::new(),
+                (Some(_), Some(_)) => {
+                    return Err(Error(String::from(
+                        "'password' and 'password_file' cannot both be set.",
+                    )))
+                }
+            };
+            Ok(Some(Auth::Basic {
+                username: basic_auth.username.clone(),
+                password,
+            }))
+        }
+        (None, Some(path)) => Ok(Some(Auth::Bearer(read_secret(path)?))),
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err(Error(String::from(
+            "'basic_auth' and 'bearer_token_file' cannot both be set.",
+        ))),
+    }
 }
 
 impl Webhook {
     fn transform_header_map(
         headers: &HashMap<String, String>,
-    ) -> Result<reqwest::header::HeaderMap<String>> {
+        syntax: config::TemplateSyntax,
+    ) -> Result<reqwest::header::HeaderMap<Template>> {
         use std::str::FromStr;
         headers
             .iter()
             .map(|(k, v)| {
                 let name = reqwest::header::HeaderName::from_str(k)
                     .map_err(|x| Error(format!("Could not parse header name: {x}")))?;
-                Ok((name, v.clone()))
+                let value = Template::new(v, syntax, config::Escape::None)
+                    .map_err(|x| Error(format!("Invalid header '{k}': {x}")))?;
+                Ok((name, value))
             })
             .collect()
     }
@@ -39,14 +228,30 @@ impl TryFrom<&config::Action> for Webhook {
             if !headers.contains_key("User-Agent") {
                 headers.insert(String::from("User-Agent"), crate::user_agent());
             }
+            // JSON bodies need escaping or placeholder values with quotes break them
+            let body_escape = web_hook.body_escape.unwrap_or_else(|| {
+                let json = headers.iter().any(|(k, v)| {
+                    k.eq_ignore_ascii_case("Content-Type") && v.contains("application/json")
+                });
+                if json {
+                    config::Escape::Json
+                } else {
+                    config::Escape::None
+                }
+            });
             if web_hook.url.is_empty() {
                 Err(Error(String::from("'url' cannot be empty.")))
             } else {
                 Ok(Self {
-                    url: web_hook.url.clone(),
+                    url: Template::new(&web_hook.url, action.template_syntax, web_hook.url_escape)
+                        .map_err(|x| Error(format!("Invalid 'url': {x}")))?,
                     method: reqwest::Method::from(web_hook.method),
-                    headers: Self::transform_header_map(&headers)?,
-                    body: web_hook.body.clone(),
+                    headers: Self::transform_header_map(&headers, action.template_syntax)?,
+                    body: Template::new(&web_hook.body, action.template_syntax, body_escape)
+                        .map_err(|x| Error(format!("Invalid 'body': {x}")))?,
+                    auth: auth(web_hook)?,
+                    client: client(web_hook)?,
+                    response_check: ResponseCheck::try_from(web_hook)?,
                 })
             }
         } else {
@@ -58,37 +263,55 @@ impl TryFrom<&config::Action> for Webhook {
 #[async_trait]
 impl Action for Webhook {
     async fn trigger(&self, placeholders: PlaceholderMap) -> Result<()> {
-        let url = crate::fill_placeholders(self.url.as_str(), &placeholders);
+        let url = self.url.fill(&placeholders);
         let headers: reqwest::header::HeaderMap = self
             .headers
             .iter()
             .map(|(k, v)| {
-                let value = HeaderValue::from_str(&crate::fill_placeholders(v, &placeholders))
+                let value = HeaderValue::from_str(&v.fill(&placeholders))
                     .map_err(|x| Error(format!("Could not parse header value: {x}")))?;
                 Ok((k.clone(), value))
             })
             .collect::<Result<_>>()?;
-        let body = crate::fill_placeholders(self.body.as_str(), &placeholders);
-        let client = reqwest::Client::new();
-        let response = client
+        let body = self.body.fill(&placeholders);
+        let mut request = self
+            .client
             .request(self.method.clone(), &url)
             .headers(headers)
-            .body(body)
-            .send()
-            .await
-            .map_err(|x| Error(format!("HTTP request failed: {x}")))?;
-        let status = response.status();
-        if status.is_success() {
-            Ok(())
-        } else {
-            Err(Error(format!(
-                "HTTP status code {} indicates error.",
-                status.as_u16()
-            )))
-        }
+            .body(body);
+        request = match &self.auth {
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, Some(password))
+            }
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        };
+        send(request, &self.response_check).await
     }
 }
 
+// Sends the request and checks the status code of the response.
+pub(super) async fn send(
+    request: reqwest::RequestBuilder,
+    response_check: &ResponseCheck,
+) -> Result<()> {
+    let response = request
+        .send()
+        .await
+        // the URL can contain secrets, e.g. the Telegram token
+        .map_err(|x| Error(format!("HTTP request failed: {}", x.without_url())))?;
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    response_check.check(status, &body).map_err(|x| {
+        let mut body: String = body.trim().chars().take(RESPONSE_BODY_MAX + 1).collect();
+        if body.chars().count() > RESPONSE_BODY_MAX {
+            body.pop();
+            body.push_str("..");
+        }
+        Error(format!("{x} Response: {body}"))
+    })
+}
+
 impl From<config::HttpMethod> for reqwest::Method {
     fn from(method: config::HttpMethod) -> Self {
         match method {
@@ -100,3 +323,93 @@ impl From<config::HttpMethod> for reqwest::Method {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn web_hook(options: &str) -> Result<Webhook> {
+        let config = config::Config::try_from(
+            format!(
+                r#"
+                [[actions]]
+                name = "Webhook"
+                type = "Webhook"
+                url = "http://example.com"
+                {options}
+                "#
+            )
+            .as_str(),
+        )
+        .unwrap();
+        Webhook::try_from(config.actions.first().unwrap())
+    }
+
+    #[test]
+    fn test_response_check() {
+        let ok = reqwest::StatusCode::OK;
+        let default = ResponseCheck::default();
+        assert!(default.check(ok, "").is_ok());
+        assert!(default
+            .check(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "")
+            .is_err());
+        let webhook = web_hook(
+            r#"
+            success_status = [200, 302]
+            success_regex = "queued|sent"
+            success_json = {pointer = "/result/ok", value = true}
+            "#,
+        )
+        .unwrap();
+        let check = |status, body| webhook.response_check.check(status, body);
+        assert!(check(ok, r#"{"result": {"ok": true}, "status": "sent"}"#).is_ok());
+        assert!(check(
+            reqwest::StatusCode::FOUND,
+            r#"{"result": {"ok": true}, "status": "queued"}"#
+        )
+        .is_ok());
+        assert_eq!(
+            check(ok, r#"{"result": {"ok": false}, "status": "sent"}"#),
+            Err(String::from(
+                "Response value at '/result/ok' is false instead of true."
+            ))
+        );
+        assert!(check(ok, r#"{"status": "sent"}"#).is_err());
+        assert!(check(ok, "sent").is_err());
+        assert!(check(ok, r#"{"result": {"ok": true}, "status": "failed"}"#).is_err());
+        assert!(check(
+            reqwest::StatusCode::CREATED,
+            r#"{"result": {"ok": true}, "status": "sent"}"#
+        )
+        .is_err());
+        assert!(web_hook(r#"success_regex = "(""#).is_err());
+    }
+
+    #[test]
+    fn test_auth() {
+        let path = std::env::temp_dir().join(format!("minmon-token-{}", std::process::id()));
+        std::fs::write(&path, "secret\n").unwrap();
+        let path = path.to_str().unwrap();
+        let webhook = web_hook(&format!(r#"bearer_token_file = "{path}""#)).unwrap();
+        assert!(matches!(webhook.auth, Some(Auth::Bearer(token)) if token == "secret"));
+        let webhook = web_hook(&format!(
+            r#"basic_auth = {{username = "user", password_file = "{path}"}}"#
+        ))
+        .unwrap();
+        assert!(matches!(
+            webhook.auth,
+            Some(Auth::Basic { username, password }) if username == "user" && password == "secret"
+        ));
+        assert!(web_hook(&format!(
+            r#"
+            bearer_token_file = "{path}"
+            basic_auth = {{username = "user", password = "secret"}}
+            "#
+        ))
+        .is_err());
+        assert!(web_hook(r#"bearer_token_file = "/nonexistent""#).is_err());
+        assert!(web_hook(r#"client_cert_file = "/nonexistent""#).is_err());
+        assert!(web_hook(r#"proxy = "http://proxy.example.com:3128""#).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
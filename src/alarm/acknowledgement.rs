@@ -0,0 +1,147 @@
+use super::AlarmKey;
+use std::collections::HashMap;
+
+struct Episode {
+    token: String,
+    acknowledged: bool,
+}
+
+// Bad episodes of the alarms that can be acknowledged, either by alarm key or by the token that
+// is part of the `ack_url` placeholder.
+#[derive(Default)]
+pub struct Acknowledgements {
+    url: Option<String>,
+    episodes: std::sync::Mutex<HashMap<AlarmKey, Episode>>,
+}
+
+impl Acknowledgements {
+    pub fn new(url: Option<String>) -> Self {
+        Self {
+            url: url.map(|x| String::from(x.trim_end_matches('/'))),
+            episodes: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn generate_token() -> String {
+        format!("{:032x}", rand::random::<u128>())
+    }
+
+    // Acknowledges the current bad episodes of the given check, optionally filtered by alarm name
+    // and ID. Returns the number of newly acknowledged alarms.
+    pub fn acknowledge(&self, check: &str, alarm: Option<&str>, id: Option<&str>) -> usize {
+        let mut count = 0;
+        for (key, episode) in self.episodes.lock().unwrap().iter_mut() {
+            if key.check == check
+                && alarm.is_none_or(|x| key.alarm == x)
+                && id.is_none_or(|x| key.id == x)
+                && !episode.acknowledged
+            {
+                episode.acknowledged = true;
+                log::info!("Alarm {key} was acknowledged.");
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Returns the alarm the token belongs to without acknowledging it.
+    pub fn token_key(&self, token: &str) -> Option<AlarmKey> {
+        let episodes = self.episodes.lock().unwrap();
+        episodes
+            .iter()
+            .find(|(_, x)| x.token == token)
+            .map(|(key, _)| key.clone())
+    }
+
+    pub fn acknowledge_token(&self, token: &str) -> Option<AlarmKey> {
+        let mut episodes = self.episodes.lock().unwrap();
+        let (key, episode) = episodes.iter_mut().find(|(_, x)| x.token == token)?;
+        if !episode.acknowledged {
+            episode.acknowledged = true;
+            log::info!("Alarm {key} was acknowledged via URL.");
+        }
+        Some(key.clone())
+    }
+}
+
+pub struct Acknowledger {
+    acknowledgements: std::sync::Arc<Acknowledgements>,
+    key: AlarmKey,
+}
+
+impl Acknowledger {
+    pub fn new(acknowledgements: std::sync::Arc<Acknowledgements>, key: AlarmKey) -> Self {
+        Self {
+            acknowledgements,
+            key,
+        }
+    }
+
+    // Starts a bad episode if there is none. Returns the `ack_url` (if configured) and whether
+    // the episode was acknowledged.
+    pub fn episode(&self) -> (Option<String>, bool) {
+        let mut episodes = self.acknowledgements.episodes.lock().unwrap();
+        let episode = episodes.entry(self.key.clone()).or_insert_with(|| Episode {
+            token: Acknowledgements::generate_token(),
+            acknowledged: false,
+        });
+        (
+            self.acknowledgements
+                .url
+                .as_ref()
+                .map(|url| format!("{url}/ack/{}", episode.token)),
+            episode.acknowledged,
+        )
+    }
+
+    pub fn reset(&self) {
+        self.acknowledgements
+            .episodes
+            .lock()
+            .unwrap()
+            .remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(id: &str) -> AlarmKey {
+        AlarmKey {
+            check: String::from("Check"),
+            alarm: String::from("Alarm"),
+            id: String::from(id),
+        }
+    }
+
+    #[test]
+    fn test_acknowledge() {
+        let acknowledgements = std::sync::Arc::new(Acknowledgements::new(Some(String::from(
+            "https://example.com/",
+        ))));
+        let a = Acknowledger::new(acknowledgements.clone(), key("a"));
+        let b = Acknowledger::new(acknowledgements.clone(), key("b"));
+        assert_eq!(acknowledgements.acknowledge("Check", None, None), 0);
+        let (url, acknowledged) = a.episode();
+        let url = url.unwrap();
+        assert!(url.starts_with("https://example.com/ack/"));
+        assert!(!acknowledged);
+        assert_eq!(a.episode().0.unwrap(), url);
+        b.episode();
+        assert_eq!(acknowledgements.acknowledge("Check", None, Some("b")), 1);
+        assert!(!a.episode().1);
+        assert!(b.episode().1);
+        let token = url.rsplit('/').next().unwrap();
+        assert_eq!(acknowledgements.token_key(token), Some(key("a")));
+        assert!(!a.episode().1);
+        assert_eq!(acknowledgements.acknowledge_token(token), Some(key("a")));
+        assert!(a.episode().1);
+        assert_eq!(acknowledgements.acknowledge_token("foo"), None);
+        // a new episode gets a new token
+        a.reset();
+        let (new_url, acknowledged) = a.episode();
+        assert_ne!(new_url.unwrap(), url);
+        assert!(!acknowledged);
+    }
+}
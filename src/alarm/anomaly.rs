@@ -0,0 +1,140 @@
+use crate::window_buffer::WindowBuffer;
+use crate::{config, Error, PlaceholderMap, Result};
+
+use super::SinkDecision;
+
+// Compares each measurement value to the mean and standard deviation of the previous
+// `window_size` values.
+pub struct Anomaly {
+    deviation: f64,
+    warm_up_cycles: u32,
+    cycles: u32,
+    window_buffer: WindowBuffer<Option<f64>>,
+    baseline: Option<(f64, f64)>,
+}
+
+impl TryFrom<&config::AlarmAnomaly> for Anomaly {
+    type Error = Error;
+
+    fn try_from(anomaly: &config::AlarmAnomaly) -> std::result::Result<Self, Self::Error> {
+        if anomaly.window_size < 2 {
+            Err(Error(String::from("'window_size' cannot be less than 2.")))
+        } else if anomaly.deviation.is_nan() || anomaly.deviation <= 0. {
+            Err(Error(String::from("'deviation' must be greater than 0.")))
+        } else {
+            let warm_up_cycles = anomaly.warm_up_cycles.unwrap_or(anomaly.window_size.into());
+            if warm_up_cycles < 2 {
+                return Err(Error(String::from(
+                    "'warm_up_cycles' cannot be less than 2.",
+                )));
+            }
+            Ok(Self {
+                deviation: anomaly.deviation,
+                warm_up_cycles,
+                cycles: 0,
+                window_buffer: WindowBuffer::try_from(&config::FilterWindowConfig {
+                    window_size: anomaly.window_size,
+                })?,
+                baseline: None,
+            })
+        }
+    }
+}
+
+impl Anomaly {
+    pub fn put_value(&mut self, value: f64) -> Result<SinkDecision> {
+        self.baseline = self.baseline();
+        self.window_buffer.push(Some(value));
+        if self.cycles < self.warm_up_cycles {
+            self.cycles += 1;
+            return Ok(SinkDecision::Good);
+        }
+        let (mean, stddev) = self
+            .baseline
+            .ok_or_else(|| Error(String::from("No baseline available.")))?;
+        Ok(if (value - mean).abs() > self.deviation * stddev {
+            SinkDecision::Bad
+        } else {
+            SinkDecision::Good
+        })
+    }
+
+    fn baseline(&self) -> Option<(f64, f64)> {
+        let values: Vec<f64> = self.window_buffer.iter().filter_map(|x| *x).collect();
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / count;
+        Some((mean, variance.sqrt()))
+    }
+
+    pub fn add_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        if let Some((mean, stddev)) = self.baseline {
+            placeholders.insert(String::from("baseline_mean"), format!("{mean:.2}"));
+            placeholders.insert(String::from("baseline_stddev"), format!("{stddev:.2}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn anomaly(window_size: u16, deviation: f64, warm_up_cycles: Option<u32>) -> Result<Anomaly> {
+        Anomaly::try_from(&config::AlarmAnomaly {
+            window_size,
+            deviation,
+            warm_up_cycles,
+        })
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(anomaly(1, 3., None).is_err());
+        assert!(anomaly(2, 0., None).is_err());
+        assert!(anomaly(2, f64::NAN, None).is_err());
+        assert!(anomaly(2, 3., Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let mut anomaly_warm_up = anomaly(4, 2., Some(3)).unwrap();
+        let mut anomaly_no_warm_up = anomaly(4, 2., Some(2)).unwrap();
+        for value in [10., 11.] {
+            assert!(matches!(
+                anomaly_warm_up.put_value(value),
+                Ok(SinkDecision::Good)
+            ));
+            assert!(matches!(
+                anomaly_no_warm_up.put_value(value),
+                Ok(SinkDecision::Good)
+            ));
+        }
+        assert!(matches!(
+            anomaly_warm_up.put_value(1000.),
+            Ok(SinkDecision::Good)
+        ));
+        assert!(matches!(
+            anomaly_no_warm_up.put_value(1000.),
+            Ok(SinkDecision::Bad)
+        ));
+    }
+
+    #[test]
+    fn test_deviation() {
+        let mut anomaly = anomaly(4, 2., None).unwrap();
+        for value in [8., 12., 8., 12.] {
+            assert!(matches!(anomaly.put_value(value), Ok(SinkDecision::Good)));
+        }
+        // mean 10, standard deviation 2
+        assert!(matches!(anomaly.put_value(14.), Ok(SinkDecision::Good)));
+        let mut placeholders = PlaceholderMap::new();
+        anomaly.add_placeholders(&mut placeholders);
+        assert_eq!(placeholders.get("baseline_mean").unwrap(), "10.00");
+        assert_eq!(placeholders.get("baseline_stddev").unwrap(), "2.00");
+        // the window rolled over, now it's 12, 8, 12, 14
+        assert!(matches!(anomaly.put_value(5.), Ok(SinkDecision::Bad)));
+    }
+}
@@ -1,25 +1,40 @@
 use crate::{measurement, Error, PlaceholderMap, Result};
 
-use super::{DataSink, SinkDecision};
+use super::{Anomaly, DataSink, Forecast, Range, SinkDecision};
 use crate::config;
 use crate::measurement::Measurement;
 
 type Item = measurement::DataSize;
 
-pub struct DataSize {
-    data_size: Item,
+pub enum DataSize {
+    Threshold(Range<Item>),
+    Forecast(Forecast),
+    Anomaly(Anomaly),
 }
 
 impl TryFrom<&config::Alarm> for DataSize {
     type Error = Error;
 
     fn try_from(alarm: &config::Alarm) -> std::result::Result<Self, Self::Error> {
-        if let config::AlarmType::DataSize(data_size) = &alarm.type_ {
-            Ok(Self {
-                data_size: Item::new(data_size.bytes())?,
-            })
-        } else {
-            Err(Error(String::from("Expected data size alarm config.")))
+        match &alarm.type_ {
+            config::AlarmType::DataSize(data_size) => Ok(Self::Threshold(Range::new(
+                data_size.min_bytes().map(Item::new).transpose()?,
+                data_size.max_bytes().map(Item::new).transpose()?,
+                "min_data_size",
+                "max_data_size",
+            )?)),
+            config::AlarmType::Forecast(forecast) => match &forecast.capacity {
+                Some(capacity) if capacity.bytes() > 0 => Ok(Self::Forecast(Forecast::new(
+                    forecast,
+                    capacity.bytes() as f64,
+                )?)),
+                Some(_) => Err(Error(String::from("'capacity' cannot be 0."))),
+                None => Err(Error(String::from(
+                    "'capacity' needs to be set for data size forecasts.",
+                ))),
+            },
+            config::AlarmType::Anomaly(anomaly) => Ok(Self::Anomaly(Anomaly::try_from(anomaly)?)),
+            _ => Err(Error(String::from("Expected data size alarm config."))),
         }
     }
 }
@@ -28,10 +43,10 @@ impl DataSink for DataSize {
     type Item = Item;
 
     fn put_data(&mut self, data: &Self::Item) -> Result<SinkDecision> {
-        Ok(if *data > self.data_size {
-            SinkDecision::Bad
-        } else {
-            SinkDecision::Good
+        Ok(match self {
+            Self::Threshold(range) => range.put_data(data),
+            Self::Forecast(forecast) => forecast.put_value(data.data() as f64),
+            Self::Anomaly(anomaly) => anomaly.put_value(data.data() as f64)?,
         })
     }
 
@@ -40,4 +55,12 @@ impl DataSink for DataSize {
         placeholders.insert(String::from("data_size_bin"), data.as_string_binary());
         placeholders.insert(String::from("data_size_dec"), data.as_string_decimal());
     }
+
+    fn add_state_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        match self {
+            Self::Threshold(_) => {}
+            Self::Forecast(forecast) => forecast.add_placeholders(placeholders),
+            Self::Anomaly(anomaly) => anomaly.add_placeholders(placeholders),
+        }
+    }
 }
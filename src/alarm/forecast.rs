@@ -0,0 +1,173 @@
+use crate::window_buffer::WindowBuffer;
+use crate::{config, duration_iso8601, Error, PlaceholderMap, Result};
+
+use super::SinkDecision;
+
+// Fits a linear regression over the last `window_size` measurements and estimates the time until
+// the measured value reaches `capacity`.
+pub struct Forecast {
+    time_to_full: std::time::Duration,
+    capacity: f64,
+    window_buffer: WindowBuffer<Option<(std::time::Instant, f64)>>,
+    estimate: Option<std::time::Duration>,
+}
+
+impl Forecast {
+    pub fn new(forecast: &config::AlarmForecast, capacity: f64) -> Result<Self> {
+        if forecast.time_to_full == 0 {
+            Err(Error(String::from("'time_to_full' cannot be 0.")))
+        } else if forecast.window_size < 2 {
+            Err(Error(String::from("'window_size' cannot be less than 2.")))
+        } else {
+            Ok(Self {
+                time_to_full: std::time::Duration::from_secs(forecast.time_to_full.into()),
+                capacity,
+                window_buffer: WindowBuffer::try_from(&config::FilterWindowConfig {
+                    window_size: forecast.window_size,
+                })?,
+                estimate: None,
+            })
+        }
+    }
+
+    pub fn put_value(&mut self, value: f64) -> SinkDecision {
+        self.put_value_at(std::time::Instant::now(), value)
+    }
+
+    fn put_value_at(&mut self, instant: std::time::Instant, value: f64) -> SinkDecision {
+        self.window_buffer.push(Some((instant, value)));
+        self.estimate = self.estimate();
+        match self.estimate {
+            Some(estimate) if estimate < self.time_to_full => SinkDecision::Bad,
+            _ => SinkDecision::Good,
+        }
+    }
+
+    // Returns `None` if there is not enough data yet or if the value is not growing.
+    fn estimate(&self) -> Option<std::time::Duration> {
+        let samples: Vec<(std::time::Instant, f64)> =
+            self.window_buffer.iter().filter_map(|x| *x).collect();
+        let (first, last) = (samples.first()?.0, samples.last()?.0);
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|(instant, value)| (instant.duration_since(first).as_secs_f64(), *value))
+            .collect();
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if variance == 0. {
+            return None;
+        }
+        let covariance: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let slope = covariance / variance;
+        if slope <= 0. {
+            return None;
+        }
+        let current = mean_y + slope * (last.duration_since(first).as_secs_f64() - mean_x);
+        let remaining = ((self.capacity - current) / slope).max(0.);
+        Some(std::time::Duration::try_from_secs_f64(remaining).unwrap_or(std::time::Duration::MAX))
+    }
+
+    pub fn add_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        if let Some(estimate) = self.estimate {
+            placeholders.insert(String::from("time_to_full"), estimate.as_secs().to_string());
+            placeholders.insert(String::from("time_to_full_iso"), duration_iso8601(estimate));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn forecast(time_to_full: u32, window_size: u16) -> Forecast {
+        Forecast::new(
+            &config::AlarmForecast {
+                time_to_full,
+                window_size,
+                capacity: None,
+            },
+            100.,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Forecast::new(
+            &config::AlarmForecast {
+                time_to_full: 0,
+                window_size: 2,
+                capacity: None,
+            },
+            100.
+        )
+        .is_err());
+        assert!(Forecast::new(
+            &config::AlarmForecast {
+                time_to_full: 1,
+                window_size: 1,
+                capacity: None,
+            },
+            100.
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_forecast_growing() {
+        let mut forecast = forecast(3600, 4);
+        let start = std::time::Instant::now();
+        let minute = std::time::Duration::from_secs(60);
+        assert!(matches!(
+            forecast.put_value_at(start, 10.),
+            SinkDecision::Good
+        ));
+        assert!(forecast.estimate.is_none());
+        // one percent per minute, 89 minutes left
+        assert!(matches!(
+            forecast.put_value_at(start + minute, 11.),
+            SinkDecision::Good
+        ));
+        assert_eq!(forecast.estimate.unwrap().as_secs(), 89 * 60);
+        assert!(matches!(
+            forecast.put_value_at(start + minute * 2, 12.),
+            SinkDecision::Good
+        ));
+        // half an hour later the value jumped up, only window of 4 is used
+        forecast.put_value_at(start + minute * 30, 40.);
+        forecast.put_value_at(start + minute * 31, 41.);
+        assert!(matches!(
+            forecast.put_value_at(start + minute * 32, 42.),
+            SinkDecision::Bad
+        ));
+        assert!(forecast.estimate.unwrap() < std::time::Duration::from_secs(3600));
+        let mut placeholders = PlaceholderMap::new();
+        forecast.add_placeholders(&mut placeholders);
+        assert!(placeholders.contains_key("time_to_full"));
+        assert_eq!(placeholders.get("time_to_full_iso").unwrap(), "PT58M");
+    }
+
+    #[test]
+    fn test_forecast_not_growing() {
+        let mut forecast = forecast(3600, 4);
+        let start = std::time::Instant::now();
+        let minute = std::time::Duration::from_secs(60);
+        forecast.put_value_at(start, 99.);
+        assert!(matches!(
+            forecast.put_value_at(start + minute, 98.),
+            SinkDecision::Good
+        ));
+        assert!(matches!(
+            forecast.put_value_at(start + minute * 2, 98.),
+            SinkDecision::Good
+        ));
+        let mut placeholders = PlaceholderMap::new();
+        forecast.add_placeholders(&mut placeholders);
+        assert!(placeholders.is_empty());
+    }
+}
@@ -1,32 +1,29 @@
 use crate::measurement::Measurement;
 use crate::{measurement, Error, PlaceholderMap, Result};
 
-use super::{DataSink, SinkDecision};
+use super::{Anomaly, DataSink, Range, SinkDecision};
 use crate::config;
 
 type Item = measurement::Integer;
 
-pub struct Integer {
-    min: Option<Item>,
-    max: Option<Item>,
+pub enum Integer {
+    Threshold(Range<Item>),
+    Anomaly(Anomaly),
 }
 
 impl TryFrom<&config::Alarm> for Integer {
     type Error = Error;
 
     fn try_from(alarm: &config::Alarm) -> std::result::Result<Self, Self::Error> {
-        if let config::AlarmType::Integer(integer) = &alarm.type_ {
-            if integer.min.is_none() && integer.max.is_none() {
-                return Err(Error(String::from(
-                    "At least one of 'min' or 'max' needs to be set",
-                )));
-            }
-            Ok(Self {
-                min: integer.min.map(Item::new).transpose()?,
-                max: integer.max.map(Item::new).transpose()?,
-            })
-        } else {
-            Err(Error(String::from("Expected integer alarm config.")))
+        match &alarm.type_ {
+            config::AlarmType::Integer(integer) => Ok(Self::Threshold(Range::new(
+                integer.min.map(Item::new).transpose()?,
+                integer.max.map(Item::new).transpose()?,
+                "min",
+                "max",
+            )?)),
+            config::AlarmType::Anomaly(anomaly) => Ok(Self::Anomaly(Anomaly::try_from(anomaly)?)),
+            _ => Err(Error(String::from("Expected integer alarm config."))),
         }
     }
 }
@@ -35,16 +32,19 @@ impl DataSink for Integer {
     type Item = Item;
 
     fn put_data(&mut self, data: &Self::Item) -> Result<SinkDecision> {
-        Ok(
-            if (self.min.is_some_and(|x| *data < x)) || (self.max.is_some_and(|x| *data > x)) {
-                SinkDecision::Bad
-            } else {
-                SinkDecision::Good
-            },
-        )
+        Ok(match self {
+            Self::Threshold(range) => range.put_data(data),
+            Self::Anomaly(anomaly) => anomaly.put_value(data.data() as f64)?,
+        })
     }
 
     fn add_placeholders(data: &Self::Item, placeholders: &mut PlaceholderMap) {
         placeholders.insert(String::from("integer"), data.to_string());
     }
+
+    fn add_state_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        if let Self::Anomaly(anomaly) = self {
+            anomaly.add_placeholders(placeholders);
+        }
+    }
 }
@@ -1,25 +1,39 @@
 use crate::measurement::Measurement;
 use crate::{measurement, Error, PlaceholderMap, Result};
 
-use super::{DataSink, SinkDecision};
+use super::{Anomaly, DataSink, Forecast, Range, SinkDecision};
 use crate::config;
 
 type Item = measurement::Level;
 
-pub struct Level {
-    level: Item,
+pub enum Level {
+    Threshold(Range<Item>),
+    Forecast(Forecast),
+    Anomaly(Anomaly),
 }
 
 impl TryFrom<&config::Alarm> for Level {
     type Error = Error;
 
     fn try_from(alarm: &config::Alarm) -> std::result::Result<Self, Self::Error> {
-        if let config::AlarmType::Level(level) = &alarm.type_ {
-            Ok(Self {
-                level: Item::new(level.level)?,
-            })
-        } else {
-            Err(Error(String::from("Expected level alarm config.")))
+        match &alarm.type_ {
+            config::AlarmType::Level(level) => Ok(Self::Threshold(Range::new(
+                level.min_level.map(Item::new).transpose()?,
+                level.max_level.map(Item::new).transpose()?,
+                "min_level",
+                "max_level",
+            )?)),
+            config::AlarmType::Forecast(forecast) => {
+                if forecast.capacity.is_some() {
+                    Err(Error(String::from(
+                        "'capacity' cannot be set for level forecasts.",
+                    )))
+                } else {
+                    Ok(Self::Forecast(Forecast::new(forecast, 100.)?))
+                }
+            }
+            config::AlarmType::Anomaly(anomaly) => Ok(Self::Anomaly(Anomaly::try_from(anomaly)?)),
+            _ => Err(Error(String::from("Expected level alarm config."))),
         }
     }
 }
@@ -28,14 +42,22 @@ impl DataSink for Level {
     type Item = Item;
 
     fn put_data(&mut self, data: &Self::Item) -> Result<SinkDecision> {
-        Ok(if *data > self.level {
-            SinkDecision::Bad
-        } else {
-            SinkDecision::Good
+        Ok(match self {
+            Self::Threshold(range) => range.put_data(data),
+            Self::Forecast(forecast) => forecast.put_value(data.data().into()),
+            Self::Anomaly(anomaly) => anomaly.put_value(data.data().into())?,
         })
     }
 
     fn add_placeholders(data: &Self::Item, placeholders: &mut PlaceholderMap) {
         placeholders.insert(String::from("level"), data.to_string());
     }
+
+    fn add_state_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        match self {
+            Self::Threshold(_) => {}
+            Self::Forecast(forecast) => forecast.add_placeholders(placeholders),
+            Self::Anomaly(anomaly) => anomaly.add_placeholders(placeholders),
+        }
+    }
 }
@@ -4,20 +4,32 @@ use crate::measurement;
 use crate::{Error, PlaceholderMap, Result};
 use async_trait::async_trait;
 
+mod acknowledgement;
+mod anomaly;
 mod binary_state;
 mod data_size;
+mod forecast;
 mod integer;
 mod level;
+mod range;
+mod silence;
 mod state_machine;
+mod state_registry;
 mod status_code;
 #[cfg(feature = "sensors")]
 mod temperature;
 
+pub use acknowledgement::{Acknowledgements, Acknowledger};
+use anomaly::Anomaly;
 pub use binary_state::BinaryState;
 pub use data_size::DataSize;
+use forecast::Forecast;
 pub use integer::Integer;
 pub use level::Level;
+use range::Range;
+pub use silence::{Silencer, Silences};
 pub use state_machine::{StateHandler, StateMachine};
+pub use state_registry::{AlarmKey, AlarmState, Dependencies, StatePublisher, StateRegistry};
 pub use status_code::StatusCode;
 #[cfg(feature = "sensors")]
 pub use temperature::Temperature;
@@ -28,6 +40,7 @@ pub trait DataSink: Send + Sync + Sized {
 
     fn put_data(&mut self, data: &Self::Item) -> Result<SinkDecision>;
     fn add_placeholders(data: &Self::Item, placeholders: &mut PlaceholderMap);
+    fn add_state_placeholders(&self, _placeholders: &mut PlaceholderMap) {}
 }
 
 pub enum SinkDecision {
@@ -51,6 +64,7 @@ pub trait Alarm: Send + Sync + Sized {
     type Item: Send + Sync;
 
     fn log_id(&self) -> &str;
+    fn set_suppressed(&mut self, suppressed: bool);
 
     async fn put_data(&mut self, data: &Self::Item, mut placeholders: PlaceholderMap)
         -> Result<()>;
@@ -73,9 +87,15 @@ where
     error_placeholders: PlaceholderMap,
     error_recover_action: Option<std::sync::Arc<dyn action::Action>>,
     error_recover_placeholders: PlaceholderMap,
+    escalation: Vec<(std::sync::Arc<dyn action::Action>, PlaceholderMap)>,
     invert: bool,
     state_machine: U,
     data_sink: T,
+    state_publisher: Option<StatePublisher>,
+    silencer: Option<Silencer>,
+    acknowledger: Option<Acknowledger>,
+    silence_summary: bool,
+    silenced: bool,
     log_id: String,
 }
 
@@ -97,9 +117,14 @@ where
         error_placeholders: PlaceholderMap,
         error_recover_action: Option<std::sync::Arc<dyn action::Action>>,
         error_recover_placeholders: PlaceholderMap,
+        escalation: Vec<(std::sync::Arc<dyn action::Action>, PlaceholderMap)>,
         invert: bool,
         state_machine: U,
         data_sink: T,
+        state_publisher: Option<StatePublisher>,
+        silencer: Option<Silencer>,
+        acknowledger: Option<Acknowledger>,
+        silence_summary: bool,
         log_id: String,
     ) -> Result<Self> {
         if name.is_empty() {
@@ -117,25 +142,95 @@ where
                 error_placeholders,
                 error_recover_action,
                 error_recover_placeholders,
+                escalation,
                 invert,
                 state_machine,
                 data_sink,
+                state_publisher,
+                silencer,
+                acknowledger,
+                silence_summary,
+                silenced: false,
                 log_id,
             })
         }
     }
 
+    fn publish_state(&self) {
+        if let Some(state_publisher) = &self.state_publisher {
+            state_publisher.publish(self.state_machine.state());
+        }
+    }
+
+    // Returns `true` if the alarm was silenced and is not anymore.
+    fn update_silenced(&mut self) -> bool {
+        let silenced = self.silencer.as_ref().is_some_and(|x| x.is_silenced());
+        let silence_ended = self.silenced && !silenced;
+        if silenced && !self.silenced {
+            log::info!("{} is silenced.", self.log_id);
+        } else if silence_ended {
+            log::info!("{} is no longer silenced.", self.log_id);
+        }
+        self.silenced = silenced;
+        silence_ended
+    }
+
+    fn is_silenced(&self) -> bool {
+        if self.silenced {
+            log::info!("{}: Not triggering action while silenced.", self.log_id);
+        }
+        self.silenced
+    }
+
+    // Adds the `ack_url` placeholder while the alarm is bad and resets the acknowledgement when it
+    // recovered. Returns `true` if the current bad episode was acknowledged.
+    fn update_acknowledgement(&self, placeholders: &mut PlaceholderMap) -> bool {
+        let Some(acknowledger) = &self.acknowledger else {
+            return false;
+        };
+        match self.state_machine.state() {
+            AlarmState::Bad => {
+                let (url, acknowledged) = acknowledger.episode();
+                if let Some(url) = url {
+                    placeholders.insert(String::from("ack_url"), url);
+                }
+                acknowledged
+            }
+            AlarmState::Good => {
+                acknowledger.reset();
+                false
+            }
+            _ => false,
+        }
+    }
+
     async fn error(&mut self, placeholders: PlaceholderMap) -> Result<()> {
-        if self.state_machine.error() {
+        let trigger = self.state_machine.error();
+        self.publish_state();
+        if trigger {
             self.trigger_error(placeholders).await?;
         }
         Ok(())
     }
 
-    async fn bad(&mut self, placeholders: PlaceholderMap) -> Result<()> {
+    async fn bad(&mut self, mut placeholders: PlaceholderMap, silence_ended: bool) -> Result<()> {
         let (trigger, trigger_error_recover) = self.state_machine.bad();
+        self.publish_state();
+        let acknowledged = self.update_acknowledgement(&mut placeholders);
         if trigger {
-            self.trigger(placeholders.clone()).await?;
+            if acknowledged {
+                log::info!("{}: Not repeating action while acknowledged.", self.log_id);
+            } else {
+                self.trigger(placeholders.clone()).await?;
+            }
+        } else if silence_ended {
+            self.trigger_silence_summary(placeholders.clone()).await?;
+        }
+        // the escalation does not advance while silenced so no step is lost
+        if !self.escalation.is_empty() && !acknowledged && !self.silenced {
+            while let Some(step) = self.state_machine.escalate() {
+                self.trigger_escalation(step, placeholders.clone()).await?;
+            }
         }
         if trigger_error_recover {
             self.trigger_error_recover(placeholders).await?;
@@ -143,10 +238,14 @@ where
         Ok(())
     }
 
-    async fn good(&mut self, placeholders: PlaceholderMap) -> Result<()> {
+    async fn good(&mut self, placeholders: PlaceholderMap, silence_ended: bool) -> Result<()> {
         let (trigger_recover, trigger_error_recover) = self.state_machine.good();
+        self.publish_state();
+        self.update_acknowledgement(&mut PlaceholderMap::new());
         if trigger_recover {
             self.trigger_recover(placeholders.clone()).await?;
+        } else if silence_ended {
+            self.trigger_silence_summary(placeholders.clone()).await?;
         }
         if trigger_error_recover {
             self.trigger_error_recover(placeholders).await?;
@@ -155,11 +254,29 @@ where
     }
 
     async fn trigger(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
         self.state_machine.add_placeholders(&mut placeholders);
         self.action.trigger(placeholders).await
     }
 
+    async fn trigger_escalation(
+        &self,
+        step: usize,
+        mut placeholders: PlaceholderMap,
+    ) -> Result<()> {
+        let (action, step_placeholders) = &self.escalation[step];
+        self.state_machine.add_placeholders(&mut placeholders);
+        placeholders.insert(String::from("escalation_step"), (step + 1).to_string());
+        crate::merge_placeholders(&mut placeholders, step_placeholders);
+        action.trigger(placeholders).await
+    }
+
     async fn trigger_recover(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
         self.state_machine.add_placeholders(&mut placeholders);
         crate::merge_placeholders(&mut placeholders, &self.recover_placeholders);
         match &self.recover_action {
@@ -169,6 +286,9 @@ where
     }
 
     async fn trigger_error(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
         self.state_machine.add_placeholders(&mut placeholders);
         crate::merge_placeholders(&mut placeholders, &self.error_placeholders);
         match &self.error_action {
@@ -178,6 +298,9 @@ where
     }
 
     async fn trigger_error_recover(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
         self.state_machine.add_placeholders(&mut placeholders);
         crate::merge_placeholders(&mut placeholders, &self.error_recover_placeholders);
         match &self.error_recover_action {
@@ -186,6 +309,15 @@ where
         }
     }
 
+    // Lets the user know the alarm is still bad after it was silenced.
+    async fn trigger_silence_summary(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if !self.silence_summary || self.state_machine.state() != AlarmState::Bad {
+            return Ok(());
+        }
+        placeholders.insert(String::from("silence_summary"), String::from("true"));
+        self.trigger(placeholders).await
+    }
+
     fn add_placeholders(&self, placeholders: &mut PlaceholderMap) {
         placeholders.insert(String::from("alarm_name"), self.name.clone());
         placeholders.insert(String::from("check_id"), self.id.clone());
@@ -205,6 +337,16 @@ where
         &self.log_id
     }
 
+    fn set_suppressed(&mut self, suppressed: bool) {
+        if let Some(state_publisher) = &self.state_publisher {
+            state_publisher.publish(if suppressed {
+                AlarmState::Suppressed
+            } else {
+                self.state_machine.state()
+            });
+        }
+    }
+
     async fn put_data(
         &mut self,
         data: &Self::Item,
@@ -217,15 +359,17 @@ where
             .unwrap_or(*data);
         T::add_placeholders(&data, &mut placeholders);
         self.add_placeholders(&mut placeholders);
+        let silence_ended = self.update_silenced();
         let mut decision = self.data_sink.put_data(&data)?;
+        self.data_sink.add_state_placeholders(&mut placeholders);
         if self.invert {
             decision = !decision;
         }
         match decision {
-            SinkDecision::Good => self.good(placeholders).await,
+            SinkDecision::Good => self.good(placeholders, silence_ended).await,
             SinkDecision::Bad => {
                 log::warn!("{}: Data is bad.", self.log_id);
-                self.bad(placeholders).await
+                self.bad(placeholders, silence_ended).await
             }
         }
     }
@@ -236,6 +380,7 @@ where
             filter.error();
         }
         self.add_placeholders(&mut placeholders);
+        self.update_silenced();
         self.error(placeholders).await
     }
 }
@@ -268,6 +413,9 @@ mod test {
             .with(eq(measurement::Level::new(20).unwrap()))
             .returning(|_| Ok(SinkDecision::Bad));
         mock_data_sink
+            .expect_add_state_placeholders()
+            .return_const(());
+        mock_data_sink
     }
 
     #[tokio::test]
@@ -319,9 +467,14 @@ mod test {
             PlaceholderMap::new(),
             Some(times_action(0)),
             PlaceholderMap::new(),
+            vec![],
             false,
             mock_state_machine,
             mock_data_sink,
+            None,
+            None,
+            None,
+            false,
             String::from(""),
         )
         .unwrap();
@@ -383,9 +536,14 @@ mod test {
             PlaceholderMap::new(),
             Some(times_action(0)),
             PlaceholderMap::new(),
+            vec![],
             false,
             mock_state_machine,
             mock_data_sink,
+            None,
+            None,
+            None,
+            false,
             String::from(""),
         )
         .unwrap();
@@ -437,9 +595,14 @@ mod test {
             PlaceholderMap::from([(String::from("Hello"), String::from("World"))]),
             Some(times_action(0)),
             PlaceholderMap::new(),
+            vec![],
             false,
             mock_state_machine,
             mock_data_sink,
+            None,
+            None,
+            None,
+            false,
             String::from(""),
         )
         .unwrap();
@@ -466,6 +629,9 @@ mod test {
             .expect_put_data()
             .with(eq(measurement::Level::new(10).unwrap()))
             .returning(|_| Ok(SinkDecision::Good));
+        mock_data_sink
+            .expect_add_state_placeholders()
+            .return_const(());
         let mut mock_action = action::MockAction::new();
         mock_action.expect_trigger().never();
         let mut mock_error_action = action::MockAction::new();
@@ -513,9 +679,14 @@ mod test {
             PlaceholderMap::new(),
             Some(std::sync::Arc::new(mock_error_recover_action)),
             PlaceholderMap::from([(String::from("Hello"), String::from("World"))]),
+            vec![],
             false,
             mock_state_machine,
             mock_data_sink,
+            None,
+            None,
+            None,
+            false,
             String::from(""),
         )
         .unwrap();
@@ -535,6 +706,142 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_escalation() {
+        let _permit = SEMAPHORE.acquire().await.unwrap();
+        let data_sink_ctx = MockDataSink::add_placeholders_context();
+        data_sink_ctx.expect().returning(
+            |data: &measurement::Level, placeholders: &mut PlaceholderMap| {
+                placeholders.insert(String::from("data"), data.to_string());
+            },
+        );
+        let mock_data_sink = mock_data_sink();
+        let mut mock_state_machine = state_machine::MockStateHandler::new();
+        mock_state_machine
+            .expect_bad()
+            .once()
+            .return_const((false, false));
+        let mut seq = mockall::Sequence::new();
+        mock_state_machine
+            .expect_escalate()
+            .once()
+            .in_sequence(&mut seq)
+            .return_const(Some(1));
+        mock_state_machine
+            .expect_escalate()
+            .once()
+            .in_sequence(&mut seq)
+            .return_const(None);
+        mock_state_machine
+            .expect_add_placeholders()
+            .once()
+            .return_const(());
+        let mut mock_escalation_action = action::MockAction::new();
+        mock_escalation_action
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                assert_eq!(placeholders.get("escalation_step").unwrap(), "2");
+                assert_eq!(placeholders.get("Hello").unwrap(), "World");
+                true
+            }))
+            .returning(|_| Ok(()));
+        let mut alarm = AlarmBase::new(
+            String::from("Name"),
+            String::from("ID"),
+            times_action(0),
+            PlaceholderMap::new(),
+            None,
+            None,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            vec![
+                (times_action(0), PlaceholderMap::new()),
+                (
+                    std::sync::Arc::new(mock_escalation_action),
+                    PlaceholderMap::from([(String::from("Hello"), String::from("World"))]),
+                ),
+            ],
+            false,
+            mock_state_machine,
+            mock_data_sink,
+            None,
+            None,
+            None,
+            false,
+            String::from(""),
+        )
+        .unwrap();
+        alarm
+            .put_data(&measurement::Level::new(20).unwrap(), PlaceholderMap::new())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acknowledgement() {
+        let _permit = SEMAPHORE.acquire().await.unwrap();
+        let data_sink_ctx = MockDataSink::add_placeholders_context();
+        data_sink_ctx.expect().return_const(());
+        let acknowledgements = std::sync::Arc::new(Acknowledgements::new(Some(String::from(
+            "http://localhost",
+        ))));
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                placeholders.get("ack_url").is_some()
+            }))
+            .returning(|_| Ok(()));
+        let mut alarm = AlarmBase::new(
+            String::from("Name"),
+            String::from("ID"),
+            std::sync::Arc::new(mock_action),
+            PlaceholderMap::new(),
+            None,
+            Some(times_action(1)),
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            vec![],
+            false,
+            StateMachine::new(1, 1, 1, 0, vec![], String::from("")).unwrap(),
+            mock_data_sink(),
+            None,
+            None,
+            Some(Acknowledger::new(
+                acknowledgements.clone(),
+                AlarmKey {
+                    check: String::from("Check"),
+                    alarm: String::from("Name"),
+                    id: String::from("ID"),
+                },
+            )),
+            false,
+            String::from(""),
+        )
+        .unwrap();
+        let bad = measurement::Level::new(20).unwrap();
+        alarm.put_data(&bad, PlaceholderMap::new()).await.unwrap();
+        assert_eq!(acknowledgements.acknowledge("Check", None, None), 1);
+        // the repeated action is not triggered
+        alarm.put_data(&bad, PlaceholderMap::new()).await.unwrap();
+        alarm
+            .put_data(&measurement::Level::new(10).unwrap(), PlaceholderMap::new())
+            .await
+            .unwrap();
+        // reset after recovery
+        assert_eq!(acknowledgements.acknowledge("Check", None, None), 0);
+        alarm.action = times_action(1);
+        alarm.put_data(&bad, PlaceholderMap::new()).await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_invert() {
         let _permit = SEMAPHORE.acquire().await.unwrap();
@@ -570,9 +877,14 @@ mod test {
             PlaceholderMap::new(),
             Some(times_action(0)),
             PlaceholderMap::new(),
+            vec![],
             true,
             mock_state_machine,
             mock_data_sink,
+            None,
+            None,
+            None,
+            false,
             String::from(""),
         )
         .unwrap();
@@ -587,4 +899,94 @@ mod test {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_silence() {
+        let _permit = SEMAPHORE.acquire().await.unwrap();
+        let data_sink_ctx = MockDataSink::add_placeholders_context();
+        data_sink_ctx.expect().return_const(());
+        let mock_data_sink = mock_data_sink();
+        let mut mock_state_machine = state_machine::MockStateHandler::new();
+        let mut sequence = mockall::Sequence::new();
+        mock_state_machine
+            .expect_bad()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_const((true, false));
+        mock_state_machine
+            .expect_bad()
+            .once()
+            .in_sequence(&mut sequence)
+            .return_const((false, false));
+        mock_state_machine
+            .expect_escalate()
+            .once()
+            .return_const(None);
+        mock_state_machine
+            .expect_state()
+            .return_const(AlarmState::Bad);
+        mock_state_machine
+            .expect_add_placeholders()
+            .once()
+            .return_const(());
+        let silences = std::sync::Arc::new(Silences::default());
+        silences.add(
+            String::from("Check"),
+            None,
+            None,
+            std::time::Duration::from_secs(60),
+        );
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                assert_eq!(placeholders.get("silence_summary").unwrap(), "true");
+                true
+            }))
+            .returning(|_| Ok(()));
+        let mut alarm = AlarmBase::new(
+            String::from("Name"),
+            String::from("ID"),
+            std::sync::Arc::new(mock_action),
+            PlaceholderMap::new(),
+            None,
+            None,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            vec![(times_action(0), PlaceholderMap::new())],
+            false,
+            mock_state_machine,
+            mock_data_sink,
+            None,
+            Some(
+                Silencer::new(
+                    silences.clone(),
+                    &[],
+                    AlarmKey {
+                        check: String::from("Check"),
+                        alarm: String::from("Name"),
+                        id: String::from("ID"),
+                    },
+                )
+                .unwrap(),
+            ),
+            None,
+            true,
+            String::from(""),
+        )
+        .unwrap();
+        alarm
+            .put_data(&measurement::Level::new(20).unwrap(), PlaceholderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(silences.remove("Check", None, None), 1);
+        alarm
+            .put_data(&measurement::Level::new(20).unwrap(), PlaceholderMap::new())
+            .await
+            .unwrap();
+    }
 }
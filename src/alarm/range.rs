@@ -0,0 +1,61 @@
+use crate::{Error, Result};
+
+use super::SinkDecision;
+
+// Range of values that are considered good. Both bounds are inclusive.
+pub struct Range<T> {
+    min: Option<T>,
+    max: Option<T>,
+}
+
+impl<T> Range<T>
+where
+    T: PartialOrd,
+{
+    pub fn new(min: Option<T>, max: Option<T>, min_name: &str, max_name: &str) -> Result<Self> {
+        match (&min, &max) {
+            (None, None) => Err(Error(format!(
+                "At least one of '{min_name}' or '{max_name}' needs to be set."
+            ))),
+            (Some(min), Some(max)) if min > max => Err(Error(format!(
+                "'{min_name}' cannot be greater than '{max_name}'."
+            ))),
+            _ => Ok(Self { min, max }),
+        }
+    }
+
+    pub fn put_data(&self, data: &T) -> SinkDecision {
+        if self.min.as_ref().is_some_and(|x| data < x)
+            || self.max.as_ref().is_some_and(|x| data > x)
+        {
+            SinkDecision::Bad
+        } else {
+            SinkDecision::Good
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(Range::<i64>::new(None, None, "min", "max").is_err());
+        assert!(Range::new(Some(2), Some(1), "min", "max").is_err());
+        assert!(Range::new(Some(1), Some(1), "min", "max").is_ok());
+    }
+
+    #[test]
+    fn test_put_data() {
+        let range = Range::new(Some(10), Some(20), "min", "max").unwrap();
+        assert!(matches!(range.put_data(&9), SinkDecision::Bad));
+        assert!(matches!(range.put_data(&10), SinkDecision::Good));
+        assert!(matches!(range.put_data(&20), SinkDecision::Good));
+        assert!(matches!(range.put_data(&21), SinkDecision::Bad));
+        let range = Range::new(None, Some(20), "min", "max").unwrap();
+        assert!(matches!(range.put_data(&-100), SinkDecision::Good));
+        let range = Range::new(Some(10), None, "min", "max").unwrap();
+        assert!(matches!(range.put_data(&100), SinkDecision::Good));
+    }
+}
@@ -0,0 +1,202 @@
+use std::str::FromStr;
+
+use super::AlarmKey;
+use crate::{config, Error, Result};
+
+pub struct MaintenanceWindow {
+    schedule: cron::Schedule,
+    duration: chrono::Duration,
+}
+
+impl TryFrom<&config::MaintenanceWindow> for MaintenanceWindow {
+    type Error = Error;
+
+    fn try_from(window: &config::MaintenanceWindow) -> std::result::Result<Self, Self::Error> {
+        if window.duration == 0 {
+            Err(Error(String::from("'duration' cannot be 0.")))
+        } else {
+            Ok(Self {
+                schedule: cron::Schedule::from_str(&window.cron)
+                    .map_err(|x| Error(format!("Invalid cron expression: {x}")))?,
+                duration: chrono::Duration::seconds(window.duration.into()),
+            })
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    // The window is active if it was started less than `duration` ago.
+    fn is_active_at(&self, now: &chrono::DateTime<chrono::Utc>) -> bool {
+        self.schedule
+            .after(&(*now - self.duration))
+            .next()
+            .is_some_and(|start| start <= *now)
+    }
+}
+
+struct Silence {
+    check: String,
+    alarm: Option<String>,
+    id: Option<String>,
+    until: std::time::Instant,
+}
+
+impl Silence {
+    fn matches(&self, key: &AlarmKey) -> bool {
+        self.check == key.check
+            && self.alarm.as_ref().is_none_or(|x| *x == key.alarm)
+            && self.id.as_ref().is_none_or(|x| *x == key.id)
+    }
+}
+
+// Global maintenance windows and the silences created at runtime.
+#[derive(Default)]
+pub struct Silences {
+    maintenance_windows: Vec<MaintenanceWindow>,
+    silences: std::sync::Mutex<Vec<Silence>>,
+}
+
+impl Silences {
+    pub fn new(maintenance_windows: &[config::MaintenanceWindow]) -> Result<Self> {
+        Ok(Self {
+            maintenance_windows: maintenance_windows
+                .iter()
+                .map(MaintenanceWindow::try_from)
+                .collect::<Result<_>>()?,
+            silences: std::sync::Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn add(
+        &self,
+        check: String,
+        alarm: Option<String>,
+        id: Option<String>,
+        duration: std::time::Duration,
+    ) {
+        self.silences.lock().unwrap().push(Silence {
+            check,
+            alarm,
+            id,
+            until: std::time::Instant::now() + duration,
+        });
+    }
+
+    // Returns the number of removed silences.
+    pub fn remove(&self, check: &str, alarm: Option<&str>, id: Option<&str>) -> usize {
+        let mut silences = self.silences.lock().unwrap();
+        let len = silences.len();
+        silences.retain(|x| {
+            !(x.check == check && x.alarm.as_deref() == alarm && x.id.as_deref() == id)
+        });
+        len - silences.len()
+    }
+
+    fn is_silenced(&self, key: &AlarmKey, now: &chrono::DateTime<chrono::Utc>) -> bool {
+        let mut silences = self.silences.lock().unwrap();
+        let instant = std::time::Instant::now();
+        silences.retain(|x| x.until > instant);
+        silences.iter().any(|x| x.matches(key))
+            || self.maintenance_windows.iter().any(|x| x.is_active_at(now))
+    }
+}
+
+// Decides whether the actions of a single alarm are muted.
+pub struct Silencer {
+    silences: std::sync::Arc<Silences>,
+    maintenance_windows: Vec<MaintenanceWindow>,
+    key: AlarmKey,
+}
+
+impl Silencer {
+    pub fn new(
+        silences: std::sync::Arc<Silences>,
+        maintenance_windows: &[config::MaintenanceWindow],
+        key: AlarmKey,
+    ) -> Result<Self> {
+        Ok(Self {
+            silences,
+            maintenance_windows: maintenance_windows
+                .iter()
+                .map(MaintenanceWindow::try_from)
+                .collect::<Result<_>>()?,
+            key,
+        })
+    }
+
+    pub fn is_silenced(&self) -> bool {
+        let now = chrono::Utc::now();
+        self.maintenance_windows
+            .iter()
+            .any(|x| x.is_active_at(&now))
+            || self.silences.is_silenced(&self.key, &now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn key(alarm: &str, id: &str) -> AlarmKey {
+        AlarmKey {
+            check: String::from("Check"),
+            alarm: String::from(alarm),
+            id: String::from(id),
+        }
+    }
+
+    #[test]
+    fn test_maintenance_window() {
+        assert!(MaintenanceWindow::try_from(&config::MaintenanceWindow {
+            cron: String::from("0 0 2 * * *"),
+            duration: 0,
+        })
+        .is_err());
+        assert!(MaintenanceWindow::try_from(&config::MaintenanceWindow {
+            cron: String::from("foo"),
+            duration: 1,
+        })
+        .is_err());
+        // every day from 02:00 to 03:00
+        let window = MaintenanceWindow::try_from(&config::MaintenanceWindow {
+            cron: String::from("0 0 2 * * *"),
+            duration: 3600,
+        })
+        .unwrap();
+        let at = |hour, minute| {
+            chrono::Utc
+                .with_ymd_and_hms(2024, 1, 1, hour, minute, 0)
+                .unwrap()
+        };
+        assert!(!window.is_active_at(&at(1, 59)));
+        assert!(window.is_active_at(&at(2, 0)));
+        assert!(window.is_active_at(&at(2, 59)));
+        assert!(!window.is_active_at(&at(3, 0)));
+    }
+
+    #[test]
+    fn test_silences() {
+        let silences = Silences::default();
+        let now = chrono::Utc::now();
+        silences.add(
+            String::from("Check"),
+            Some(String::from("Alarm")),
+            None,
+            std::time::Duration::from_secs(60),
+        );
+        silences.add(
+            String::from("Check"),
+            None,
+            Some(String::from("/")),
+            std::time::Duration::ZERO,
+        );
+        assert!(silences.is_silenced(&key("Alarm", "/home"), &now));
+        assert!(!silences.is_silenced(&key("Other", "/home"), &now));
+        // the second silence already expired
+        assert!(!silences.is_silenced(&key("Other", "/"), &now));
+        assert_eq!(silences.remove("Check", Some("Alarm"), Some("/home")), 0);
+        assert_eq!(silences.remove("Check", Some("Alarm"), None), 1);
+        assert!(!silences.is_silenced(&key("Alarm", "/home"), &now));
+    }
+}
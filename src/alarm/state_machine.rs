@@ -1,12 +1,16 @@
+use super::AlarmState;
 use crate::{datetime_iso8601, duration_iso8601, Error, PlaceholderMap, Result};
 
 #[cfg_attr(test, mockall::automock)]
 pub trait StateHandler: Send + Sync + Sized {
     fn add_placeholders(&self, placeholders: &mut PlaceholderMap);
+    fn state(&self) -> AlarmState;
 
     fn error(&mut self) -> bool;
     fn bad(&mut self) -> (bool, bool);
     fn good(&mut self) -> (bool, bool);
+    // Returns the index of the escalation step that is due, if any.
+    fn escalate(&mut self) -> Option<usize>;
 }
 
 pub struct StateMachine {
@@ -14,6 +18,8 @@ pub struct StateMachine {
     repeat_cycles: u32,
     recover_cycles: u32,
     error_repeat_cycles: u32,
+    // time in bad state after which each escalation step is triggered
+    escalation: Vec<std::time::Duration>,
     state: State,
     log_id: String,
 }
@@ -67,6 +73,7 @@ struct BadState {
     last_state_duration: std::time::Duration,
     cycles: u32,
     good_cycles: u32,
+    escalation_step: usize,
 }
 
 #[derive(Clone)]
@@ -83,18 +90,24 @@ impl StateMachine {
         repeat_cycles: u32,
         recover_cycles: u32,
         error_repeat_cycles: u32,
+        escalation: Vec<std::time::Duration>,
         log_id: String,
     ) -> Result<Self> {
         if cycles == 0 {
             Err(Error(String::from("'cycles' cannot be 0.")))
         } else if recover_cycles == 0 {
             Err(Error(String::from("'recover_cycles' cannot be 0.")))
+        } else if escalation.windows(2).any(|x| x[0] >= x[1]) {
+            Err(Error(String::from(
+                "The escalation steps need to be in ascending order of 'after'.",
+            )))
         } else {
             Ok(Self {
                 cycles,
                 repeat_cycles,
                 recover_cycles,
                 error_repeat_cycles,
+                escalation,
                 state: State::default(),
                 log_id,
             })
@@ -103,6 +116,14 @@ impl StateMachine {
 }
 
 impl StateHandler for StateMachine {
+    fn state(&self) -> AlarmState {
+        match &self.state {
+            State::Good(_) => AlarmState::Good,
+            State::Bad(_) => AlarmState::Bad,
+            State::Error(_) => AlarmState::Error,
+        }
+    }
+
     fn add_placeholders(&self, placeholders: &mut PlaceholderMap) {
         match &self.state {
             State::Bad(bad) => {
@@ -253,6 +274,7 @@ impl StateHandler for StateMachine {
                         last_state_duration: good.instant.elapsed(),
                         cycles: 1,
                         good_cycles: 0,
+                        escalation_step: 0,
                     })
                 } else {
                     State::Good(GoodState {
@@ -335,6 +357,25 @@ impl StateHandler for StateMachine {
         };
         (trigger, trigger_error_recover)
     }
+
+    fn escalate(&mut self) -> Option<usize> {
+        let State::Bad(bad) = &mut self.state else {
+            return None;
+        };
+        let step = bad.escalation_step;
+        let after = self.escalation.get(step)?;
+        if bad.instant.elapsed() < *after {
+            return None;
+        }
+        bad.escalation_step += 1;
+        log::warn!(
+            "{} is escalated to step {} after being bad for {} seconds.",
+            self.log_id,
+            step + 1,
+            after.as_secs()
+        );
+        Some(step)
+    }
 }
 
 #[cfg(test)]
@@ -344,24 +385,24 @@ mod test {
     #[test]
     fn test_validation() {
         assert!(matches!(
-            StateMachine::new(0, 0, 1, 0, String::from("")),
+            StateMachine::new(0, 0, 1, 0, vec![], String::from("")),
             Err(Error(_))
         ));
         assert!(matches!(
-            StateMachine::new(1, 0, 0, 0, String::from("")),
+            StateMachine::new(1, 0, 0, 0, vec![], String::from("")),
             Err(Error(_))
         ));
     }
 
     #[test]
     fn test_trigger_action() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         assert_eq!((true, false), state_machine.bad());
     }
 
     #[test]
     fn test_reset_bad_cycles_in_good_state() {
-        let mut state_machine = StateMachine::new(2, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(2, 0, 1, 0, vec![], String::from("")).unwrap();
         assert_eq!((false, false), state_machine.bad());
         assert_eq!((false, false), state_machine.good());
         assert_eq!((false, false), state_machine.bad());
@@ -369,7 +410,7 @@ mod test {
 
     #[test]
     fn test_trigger_action_repeat() {
-        let mut state_machine = StateMachine::new(1, 7, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 7, 1, 0, vec![], String::from("")).unwrap();
         assert_eq!((true, false), state_machine.bad());
         for _ in 0..6 {
             assert_eq!((false, false), state_machine.bad());
@@ -379,7 +420,7 @@ mod test {
 
     #[test]
     fn test_trigger_recover_action() {
-        let mut state_machine = StateMachine::new(1, 0, 5, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 5, 0, vec![], String::from("")).unwrap();
         assert_eq!((true, false), state_machine.bad());
         for _ in 0..4 {
             assert_eq!((false, false), state_machine.good());
@@ -389,13 +430,13 @@ mod test {
 
     #[test]
     fn test_trigger_error_action() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         assert!(state_machine.error());
     }
 
     #[test]
     fn test_trigger_error_action_repeat() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 7, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 7, vec![], String::from("")).unwrap();
         assert!(state_machine.error());
         for _ in 0..6 {
             assert!(!state_machine.error());
@@ -405,14 +446,14 @@ mod test {
 
     #[test]
     fn test_trigger_error_recover_action() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         state_machine.error();
         assert_eq!((false, true), state_machine.good());
     }
 
     #[test]
     fn test_add_placeholders_good() {
-        let state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         let mut placeholders = PlaceholderMap::new();
         state_machine.add_placeholders(&mut placeholders);
         use std::str::FromStr;
@@ -424,7 +465,7 @@ mod test {
 
     #[test]
     fn test_add_placeholders_bad() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         let mut placeholders = PlaceholderMap::new();
         state_machine.bad();
         state_machine.add_placeholders(&mut placeholders);
@@ -439,7 +480,7 @@ mod test {
 
     #[test]
     fn test_add_placeholders_error_shadowed_good() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         let mut placeholders = PlaceholderMap::new();
         state_machine.error();
         state_machine.add_placeholders(&mut placeholders);
@@ -456,7 +497,7 @@ mod test {
 
     #[test]
     fn test_add_placeholders_error_shadowed_bad() {
-        let mut state_machine = StateMachine::new(1, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap();
         let mut placeholders = PlaceholderMap::new();
         state_machine.bad();
         state_machine.error();
@@ -477,7 +518,7 @@ mod test {
 
     #[test]
     fn test_trigger_error_shadowed_good() {
-        let mut state_machine = StateMachine::new(2, 0, 1, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(2, 0, 1, 0, vec![], String::from("")).unwrap();
         assert!(matches!(state_machine.state, State::Good(_)));
         state_machine.error();
         assert!(matches!(state_machine.state, State::Error(_)));
@@ -487,7 +528,7 @@ mod test {
 
     #[test]
     fn test_trigger_error_shadowed_bad() {
-        let mut state_machine = StateMachine::new(1, 0, 2, 0, String::from("")).unwrap();
+        let mut state_machine = StateMachine::new(1, 0, 2, 0, vec![], String::from("")).unwrap();
         state_machine.bad();
         assert!(matches!(state_machine.state, State::Bad(_)));
         state_machine.error();
@@ -495,4 +536,29 @@ mod test {
         state_machine.good();
         assert!(matches!(state_machine.state, State::Bad(_)));
     }
+
+    #[test]
+    fn test_escalation() {
+        let seconds = std::time::Duration::from_secs;
+        assert!(
+            StateMachine::new(1, 0, 1, 0, vec![seconds(2), seconds(1)], String::from("")).is_err()
+        );
+        let mut state_machine = StateMachine::new(
+            1,
+            0,
+            1,
+            0,
+            vec![seconds(0), seconds(3600)],
+            String::from(""),
+        )
+        .unwrap();
+        assert_eq!(state_machine.escalate(), None);
+        state_machine.bad();
+        assert_eq!(state_machine.escalate(), Some(0));
+        assert_eq!(state_machine.escalate(), None);
+        // reset on recovery
+        state_machine.good();
+        state_machine.bad();
+        assert_eq!(state_machine.escalate(), Some(0));
+    }
 }
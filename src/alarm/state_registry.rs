@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AlarmState {
+    Good,
+    Bad,
+    Error,
+    Suppressed,
+}
+
+impl std::fmt::Display for AlarmState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AlarmState::Good => "Good",
+                AlarmState::Bad => "Bad",
+                AlarmState::Error => "Error",
+                AlarmState::Suppressed => "Suppressed",
+            }
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct AlarmKey {
+    pub check: String,
+    pub alarm: String,
+    pub id: String,
+}
+
+impl std::fmt::Display for AlarmKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}[{}]", self.check, self.alarm, self.id)
+    }
+}
+
+// Shared between the checks' tasks so alarm states can be observed from outside of the checks.
+#[derive(Default)]
+pub struct StateRegistry {
+    states: std::sync::Mutex<HashMap<AlarmKey, AlarmState>>,
+}
+
+impl StateRegistry {
+    pub fn set(&self, key: &AlarmKey, state: AlarmState) {
+        self.states.lock().unwrap().insert(key.clone(), state);
+    }
+
+    // Returns all registered alarms of the given check, optionally filtered by alarm name and ID.
+    pub fn matching(
+        &self,
+        check: &str,
+        alarm: Option<&str>,
+        id: Option<&str>,
+    ) -> Vec<(AlarmKey, AlarmState)> {
+        let mut res: Vec<(AlarmKey, AlarmState)> = self
+            .states
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| {
+                key.check == check
+                    && alarm.is_none_or(|x| key.alarm == x)
+                    && id.is_none_or(|x| key.id == x)
+            })
+            .map(|(key, state)| (key.clone(), *state))
+            .collect();
+        res.sort_by(|a, b| (&a.0.alarm, &a.0.id).cmp(&(&b.0.alarm, &b.0.id)));
+        res
+    }
+}
+
+pub struct StatePublisher {
+    registry: std::sync::Arc<StateRegistry>,
+    key: AlarmKey,
+}
+
+impl StatePublisher {
+    pub fn new(registry: std::sync::Arc<StateRegistry>, key: AlarmKey) -> Self {
+        registry.set(&key, AlarmState::Good);
+        Self { registry, key }
+    }
+
+    pub fn publish(&self, state: AlarmState) {
+        self.registry.set(&self.key, state);
+    }
+}
+
+// Alarms of other checks that suppress the alarms of a check while they are not good.
+pub struct Dependencies {
+    registry: std::sync::Arc<StateRegistry>,
+    dependencies: Vec<(String, Option<String>)>,
+}
+
+impl Dependencies {
+    pub fn new(
+        registry: std::sync::Arc<StateRegistry>,
+        dependencies: Vec<(String, Option<String>)>,
+    ) -> Self {
+        Self {
+            registry,
+            dependencies,
+        }
+    }
+
+    // Returns the alarms that are currently bad, erroring or suppressed themselves.
+    pub fn suppressing(&self) -> Vec<AlarmKey> {
+        self.dependencies
+            .iter()
+            .flat_map(|(check, alarm)| self.registry.matching(check, alarm.as_deref(), None))
+            .filter(|(_, state)| *state != AlarmState::Good)
+            .map(|(key, _)| key)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(check: &str, alarm: &str, id: &str) -> AlarmKey {
+        AlarmKey {
+            check: String::from(check),
+            alarm: String::from(alarm),
+            id: String::from(id),
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let registry = std::sync::Arc::new(StateRegistry::default());
+        let publisher = StatePublisher::new(registry.clone(), key("Check", "Alarm", "/home"));
+        StatePublisher::new(registry.clone(), key("Check", "Alarm", "/"));
+        StatePublisher::new(registry.clone(), key("Check", "Other", "/"));
+        publisher.publish(AlarmState::Bad);
+        assert_eq!(
+            registry.matching("Check", Some("Alarm"), None),
+            vec![
+                (key("Check", "Alarm", "/"), AlarmState::Good),
+                (key("Check", "Alarm", "/home"), AlarmState::Bad)
+            ]
+        );
+        assert_eq!(
+            registry.matching("Check", Some("Alarm"), Some("/home")),
+            vec![(key("Check", "Alarm", "/home"), AlarmState::Bad)]
+        );
+        assert!(registry.matching("Check", Some("Missing"), None).is_empty());
+        assert_eq!(registry.matching("Check", None, None).len(), 3);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let registry = std::sync::Arc::new(StateRegistry::default());
+        let publisher = StatePublisher::new(registry.clone(), key("Uplink", "Alarm", "eth0"));
+        StatePublisher::new(registry.clone(), key("Other", "Alarm", "eth0"))
+            .publish(AlarmState::Error);
+        let dependencies =
+            Dependencies::new(registry.clone(), vec![(String::from("Uplink"), None)]);
+        assert!(dependencies.suppressing().is_empty());
+        publisher.publish(AlarmState::Bad);
+        assert_eq!(
+            dependencies.suppressing(),
+            vec![key("Uplink", "Alarm", "eth0")]
+        );
+        publisher.publish(AlarmState::Suppressed);
+        assert_eq!(dependencies.suppressing().len(), 1);
+        publisher.publish(AlarmState::Good);
+        assert!(dependencies.suppressing().is_empty());
+    }
+}
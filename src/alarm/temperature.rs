@@ -1,25 +1,29 @@
 use crate::{measurement, Error, PlaceholderMap, Result};
 
-use super::{DataSink, SinkDecision};
+use super::{Anomaly, DataSink, Range, SinkDecision};
 use crate::config;
 use crate::measurement::Measurement;
 
 type Item = measurement::Temperature;
 
-pub struct Temperature {
-    temperature: Item,
+pub enum Temperature {
+    Threshold(Range<Item>),
+    Anomaly(Anomaly),
 }
 
 impl TryFrom<&config::Alarm> for Temperature {
     type Error = Error;
 
     fn try_from(alarm: &config::Alarm) -> std::result::Result<Self, Self::Error> {
-        if let config::AlarmType::Temperature(temperature) = &alarm.type_ {
-            Ok(Self {
-                temperature: Item::new(temperature.temperature)?,
-            })
-        } else {
-            Err(Error(String::from("Expected temperature alarm config.")))
+        match &alarm.type_ {
+            config::AlarmType::Temperature(temperature) => Ok(Self::Threshold(Range::new(
+                temperature.min_temperature.map(Item::new).transpose()?,
+                temperature.max_temperature.map(Item::new).transpose()?,
+                "min_temperature",
+                "max_temperature",
+            )?)),
+            config::AlarmType::Anomaly(anomaly) => Ok(Self::Anomaly(Anomaly::try_from(anomaly)?)),
+            _ => Err(Error(String::from("Expected temperature alarm config."))),
         }
     }
 }
@@ -28,14 +32,19 @@ impl DataSink for Temperature {
     type Item = Item;
 
     fn put_data(&mut self, data: &Self::Item) -> Result<SinkDecision> {
-        Ok(if *data > self.temperature {
-            SinkDecision::Bad
-        } else {
-            SinkDecision::Good
+        Ok(match self {
+            Self::Threshold(range) => range.put_data(data),
+            Self::Anomaly(anomaly) => anomaly.put_value(data.data().into())?,
         })
     }
 
     fn add_placeholders(data: &Self::Item, placeholders: &mut PlaceholderMap) {
         placeholders.insert(String::from("temperature"), data.to_string());
     }
+
+    fn add_state_placeholders(&self, placeholders: &mut PlaceholderMap) {
+        if let Self::Anomaly(anomaly) = self {
+            anomaly.add_placeholders(placeholders);
+        }
+    }
 }
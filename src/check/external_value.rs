@@ -0,0 +1,136 @@
+use super::DataSource;
+use crate::ingestion::{Inbox, Ingest};
+use crate::{config, measurement};
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+pub struct ExternalValue<T>
+where
+    T: measurement::Measurement,
+{
+    ids: Vec<String>,
+    inbox: std::sync::Arc<Inbox<T>>,
+}
+
+impl<T> TryFrom<&config::Check> for ExternalValue<T>
+where
+    T: measurement::Measurement + Send + Sync + 'static,
+    T::Data: std::str::FromStr,
+    <T::Data as std::str::FromStr>::Err: std::fmt::Display,
+{
+    type Error = Error;
+
+    fn try_from(check: &config::Check) -> std::result::Result<Self, Self::Error> {
+        if let config::CheckType::ExternalValue(external_value) = &check.type_ {
+            // the ingestion protocol separates check name, id and value by whitespace
+            if check.name.contains(char::is_whitespace) {
+                Err(Error(String::from(
+                    "The name of an ExternalValue check cannot contain whitespace.",
+                )))
+            } else if external_value.ids.is_empty() {
+                Err(Error(String::from("'ids' cannot be empty.")))
+            } else if external_value
+                .ids
+                .iter()
+                .any(|x| x.contains(char::is_whitespace))
+            {
+                Err(Error(String::from("'ids' cannot contain whitespace.")))
+            } else {
+                Ok(Self {
+                    ids: external_value.ids.clone(),
+                    inbox: std::sync::Arc::new(Inbox::new(external_value.ids.clone())),
+                })
+            }
+        } else {
+            panic!();
+        }
+    }
+}
+
+#[async_trait]
+impl<T> DataSource for ExternalValue<T>
+where
+    T: measurement::Measurement + Send + Sync + 'static,
+    T::Data: std::str::FromStr,
+    <T::Data as std::str::FromStr>::Err: std::fmt::Display,
+{
+    type Item = T;
+
+    async fn get_data(
+        &mut self,
+        _placeholders: &mut PlaceholderMap,
+    ) -> Result<Vec<Result<Option<Self::Item>>>> {
+        Ok(self.inbox.take().into_iter().map(Ok).collect())
+    }
+
+    fn inbox(&self) -> Option<std::sync::Arc<dyn Ingest>> {
+        Some(self.inbox.clone())
+    }
+
+    fn format_data(&self, data: &Self::Item) -> String {
+        format!("external value {data}")
+    }
+
+    fn ids(&self) -> &[String] {
+        &self.ids[..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use measurement::Measurement;
+
+    #[tokio::test]
+    async fn test_external_value() {
+        let check = config::Config::try_from(
+            r#"
+            [[checks]]
+            name = "ExternalValueTest"
+            type = "ExternalValue"
+            value_type = "Integer"
+            ids = ["a", "b"]
+            "#,
+        )
+        .unwrap()
+        .checks
+        .remove(0);
+        let mut external_value = ExternalValue::<measurement::Integer>::try_from(&check).unwrap();
+        external_value.inbox.push("b", "-5").unwrap();
+        let mut placeholders = PlaceholderMap::new();
+        let data = external_value.get_data(&mut placeholders).await.unwrap();
+        assert_eq!(data[0].as_ref().unwrap(), &None);
+        assert_eq!(
+            data[1].as_ref().unwrap(),
+            &Some(measurement::Integer::new(-5).unwrap())
+        );
+        let data = external_value.get_data(&mut placeholders).await.unwrap();
+        assert_eq!(data[1].as_ref().unwrap(), &None);
+    }
+
+    #[test]
+    fn test_validation() {
+        let check = |name: &str, ids: &str| {
+            let check = config::Config::try_from(
+                format!(
+                    r#"
+                    [[checks]]
+                    name = "{name}"
+                    type = "ExternalValue"
+                    value_type = "Integer"
+                    ids = {ids}
+                    "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+            .checks
+            .remove(0);
+            ExternalValue::<measurement::Integer>::try_from(&check).map(|_| ())
+        };
+        assert!(check("Value", r#"["a"]"#).is_ok());
+        assert!(check("Value", "[]").is_err());
+        assert!(check("Value", r#"["a b"]"#).is_err());
+        assert!(check("External value", r#"["a"]"#).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use super::DataSource;
+use crate::{config, measurement};
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+use measurement::Measurement;
+
+#[derive(Clone)]
+struct Ping {
+    instant: std::time::Instant,
+    exit_code: Option<i32>,
+    message: Option<String>,
+}
+
+type PingMap = std::collections::HashMap<String, Ping>;
+
+const ACCEPT_ERROR_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub struct Heartbeat {
+    names: Vec<String>,
+    start: std::time::Instant,
+    pings: std::sync::Arc<std::sync::Mutex<PingMap>>,
+    // last ping of each name as of the last `get_data`
+    reported: Vec<Option<Ping>>,
+    socket_path: std::path::PathBuf,
+    socket_mode: u32,
+}
+
+impl TryFrom<&config::Check> for Heartbeat {
+    type Error = Error;
+
+    fn try_from(check: &config::Check) -> std::result::Result<Self, Self::Error> {
+        if let config::CheckType::Heartbeat(heartbeat) = &check.type_ {
+            if heartbeat.names.is_empty() {
+                return Err(Error(String::from("'names' cannot be empty.")));
+            }
+            if heartbeat
+                .names
+                .iter()
+                .any(|x| x.contains(char::is_whitespace))
+            {
+                return Err(Error(String::from("'names' cannot contain whitespace.")));
+            }
+            Ok(Self {
+                names: heartbeat.names.clone(),
+                start: std::time::Instant::now(),
+                pings: std::sync::Arc::new(std::sync::Mutex::new(PingMap::new())),
+                reported: vec![None; heartbeat.names.len()],
+                socket_path: std::path::PathBuf::from(&heartbeat.socket_path),
+                socket_mode: heartbeat.socket_mode,
+            })
+        } else {
+            panic!();
+        }
+    }
+}
+
+impl Heartbeat {
+    fn listen(&mut self) -> Result<()> {
+        let listener = tokio::net::UnixListener::from_std(crate::bind_unix_socket(
+            &self.socket_path,
+            self.socket_mode,
+        )?)
+        .map_err(|x| Error(x.to_string()))?;
+        let names = self.names.clone();
+        let pings = self.pings.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let names = names.clone();
+                        let pings = pings.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = handle_connection(stream, &names, &pings).await {
+                                log::warn!("Heartbeat connection failed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        log::error!("Heartbeat listener failed: {err}");
+                        tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn get_ping_data(&self, ping: Option<&Ping>) -> Result<i64> {
+        let last = match ping {
+            Some(ping) => ping.instant,
+            None => self.start,
+        };
+        if let Some(exit_code) = ping.and_then(|x| x.exit_code) {
+            if exit_code != 0 {
+                return Err(Error(format!(
+                    "Heartbeat reported exit code {exit_code}{}",
+                    ping.and_then(|x| x.message.as_ref())
+                        .map(|x| format!(": {x}"))
+                        .unwrap_or_default()
+                )));
+            }
+        }
+        Ok(last.elapsed().as_secs() as i64)
+    }
+}
+
+// A ping is a single line: `<name> [<exit code> [<message>]]`
+fn parse_ping(line: &str, names: &[String]) -> Result<(String, Ping)> {
+    let mut parts = line.trim().splitn(3, char::is_whitespace);
+    let name = parts.next().unwrap_or_default();
+    if !names.iter().any(|x| x == name) {
+        return Err(Error(format!("Unknown heartbeat name '{name}'.")));
+    }
+    let exit_code = parts
+        .next()
+        .map(|x| x.parse::<i32>())
+        .transpose()
+        .map_err(|x| Error(format!("Invalid exit code: {x}")))?;
+    let message = parts.next().map(|x| String::from(x.trim()));
+    Ok((
+        String::from(name),
+        Ping {
+            instant: std::time::Instant::now(),
+            exit_code,
+            message,
+        },
+    ))
+}
+
+async fn handle_connection(
+    stream: tokio::net::UnixStream,
+    names: &[String],
+    pings: &std::sync::Mutex<PingMap>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    tokio::io::BufReader::new(reader)
+        .read_line(&mut line)
+        .await
+        .map_err(|x| Error(x.to_string()))?;
+    let response = match parse_ping(&line, names) {
+        Ok((name, ping)) => {
+            log::debug!("Got heartbeat '{name}'.");
+            pings.lock().unwrap().insert(name, ping);
+            String::from("OK\n")
+        }
+        Err(err) => format!("Error: {err}\n"),
+    };
+    writer
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|x| Error(x.to_string()))
+}
+
+#[async_trait]
+impl DataSource for Heartbeat {
+    type Item = measurement::Integer;
+
+    async fn get_data(
+        &mut self,
+        _placeholders: &mut PlaceholderMap,
+    ) -> Result<Vec<Result<Option<Self::Item>>>> {
+        let pings = self.pings.lock().unwrap();
+        self.reported = self.names.iter().map(|x| pings.get(x).cloned()).collect();
+        drop(pings);
+        Ok(self
+            .reported
+            .iter()
+            .map(|ping| {
+                self.get_ping_data(ping.as_ref())
+                    .and_then(Self::Item::new)
+                    .map(Some)
+            })
+            .collect())
+    }
+
+    fn add_id_placeholders(&self, index: usize, placeholders: &mut PlaceholderMap) {
+        let Some(ping) = &self.reported[index] else {
+            return;
+        };
+        if let Some(message) = &ping.message {
+            placeholders.insert(String::from("heartbeat_message"), message.clone());
+        }
+        if let Some(exit_code) = ping.exit_code {
+            placeholders.insert(String::from("heartbeat_exit_code"), exit_code.to_string());
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.start = std::time::Instant::now();
+        self.listen()
+    }
+
+    fn format_data(&self, data: &Self::Item) -> String {
+        format!("{data} seconds since the last ping")
+    }
+
+    fn ids(&self) -> &[String] {
+        &self.names[..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_ping() {
+        let names = vec![String::from("backup")];
+        assert!(parse_ping("foo", &names).is_err());
+        assert!(parse_ping("backup foo", &names).is_err());
+        let (name, ping) = parse_ping("backup\n", &names).unwrap();
+        assert_eq!(name, "backup");
+        assert_eq!(ping.exit_code, None);
+        assert_eq!(ping.message, None);
+        let (_, ping) = parse_ping("backup 1 disk  full\n", &names).unwrap();
+        assert_eq!(ping.exit_code, Some(1));
+        assert_eq!(ping.message.as_deref(), Some("disk  full"));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        let path =
+            std::env::temp_dir().join(format!("minmon-heartbeat-{}.sock", std::process::id()));
+        let check = config::Config::try_from(
+            format!(
+                r#"
+                [[checks]]
+                name = "Heartbeat"
+                type = "Heartbeat"
+                socket_path = "{}"
+                names = ["backup", "sync"]
+                "#,
+                path.display()
+            )
+            .as_str(),
+        )
+        .unwrap()
+        .checks
+        .remove(0);
+        let mut heartbeat = Heartbeat::try_from(&check).unwrap();
+        // nothing is bound before the check is started
+        assert!(!path.exists());
+        heartbeat.start().unwrap();
+        let mut placeholders = PlaceholderMap::new();
+        assert!(heartbeat.get_data(&mut placeholders).await.is_ok());
+        let ping = |line: &'static str| {
+            let path = path.clone();
+            async move {
+                let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+                stream.write_all(line.as_bytes()).await.unwrap();
+                let mut response = String::new();
+                tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+                    .await
+                    .unwrap();
+                response
+            }
+        };
+        assert_eq!(ping("backup 0 done\n").await, "OK\n");
+        assert!(ping("foo\n").await.starts_with("Error: "));
+        assert_eq!(ping("sync 2 failed\n").await, "OK\n");
+        let data = heartbeat.get_data(&mut placeholders).await.unwrap();
+        assert_eq!(
+            data[0].as_ref().unwrap().unwrap(),
+            measurement::Integer::new(0).unwrap()
+        );
+        assert!(data[1].is_err());
+        let id_placeholders = |index: usize| {
+            let mut placeholders = PlaceholderMap::new();
+            heartbeat.add_id_placeholders(index, &mut placeholders);
+            placeholders
+        };
+        assert_eq!(
+            id_placeholders(0),
+            PlaceholderMap::from([
+                (String::from("heartbeat_message"), String::from("done")),
+                (String::from("heartbeat_exit_code"), String::from("0")),
+            ])
+        );
+        assert_eq!(
+            id_placeholders(1).get("heartbeat_message").unwrap(),
+            "failed"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
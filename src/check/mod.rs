@@ -4,14 +4,18 @@ use crate::alarm::{Alarm, AlarmBase, DataSink};
 use crate::config;
 use crate::filter;
 use crate::filter::FilterFactory;
+use crate::ingestion;
 use crate::measurement;
+use crate::staleness;
 use crate::ActionMap;
 use crate::{Error, PlaceholderMap, Result};
 use async_trait::async_trait;
 
 #[cfg(feature = "docker")]
 mod docker_container_status;
+mod external_value;
 mod filesystem_usage;
+mod heartbeat;
 mod memory_usage;
 mod network_throughput;
 mod pressure_average;
@@ -24,9 +28,19 @@ mod temperature;
 
 #[async_trait]
 pub trait Check: Send + Sync {
+    // Called once before the check is triggered for the first time.
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
     async fn trigger(&mut self);
     fn interval(&self) -> std::time::Duration;
     fn name(&self) -> &str;
+    // Name of the check this one belongs to. Both are enabled and disabled together.
+    fn parent(&self) -> Option<&str> {
+        None
+    }
+    // Called instead of `trigger` while the check is disabled.
+    fn disabled(&mut self) {}
 }
 
 #[async_trait]
@@ -37,6 +51,16 @@ pub trait DataSource: Send + Sync {
         &mut self,
         placeholders: &mut PlaceholderMap,
     ) -> Result<Vec<Result<Option<Self::Item>>>>;
+    // Inbox for the values pushed through the ingestion socket.
+    fn inbox(&self) -> Option<std::sync::Arc<dyn ingestion::Ingest>> {
+        None
+    }
+    // Called once before the first `get_data`, e.g. to start listening on a socket.
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+    // Adds the placeholders that belong to the ID at `index` of the last `get_data`.
+    fn add_id_placeholders(&self, _index: usize, _placeholders: &mut PlaceholderMap) {}
     fn format_data(&self, data: &Self::Item) -> String;
     fn ids(&self) -> &[String];
 }
@@ -53,6 +77,9 @@ where
     filter: Option<Vec<Box<dyn filter::Filter<T::Item>>>>,
     data_source: T,
     alarms: Vec<Vec<U>>,
+    dependencies: Option<alarm::Dependencies>,
+    suppressed: bool,
+    last_data: Option<std::sync::Arc<staleness::LastData>>,
 }
 
 impl<T, U> CheckBase<T, U>
@@ -68,6 +95,8 @@ where
         filter: Option<Vec<Box<dyn filter::Filter<T::Item>>>>,
         data_source: T,
         alarms: Vec<Vec<U>>,
+        dependencies: Option<alarm::Dependencies>,
+        last_data: Option<std::sync::Arc<staleness::LastData>>,
     ) -> Result<Self> {
         if interval.is_zero() {
             Err(Error(String::from("'interval' cannot be 0.")))
@@ -93,9 +122,41 @@ where
                 filter,
                 data_source,
                 alarms,
+                dependencies,
+                suppressed: false,
+                last_data,
             })
         }
     }
+
+    // Returns `true` if any of the alarms this check depends on is not in the good state.
+    fn update_suppressed(&mut self) -> bool {
+        let suppressing = match &self.dependencies {
+            Some(dependencies) => dependencies.suppressing(),
+            None => return false,
+        };
+        let suppressed = !suppressing.is_empty();
+        if suppressed != self.suppressed {
+            if suppressed {
+                log::info!(
+                    "Check '{}' is suppressed by {}.",
+                    self.name,
+                    suppressing
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            } else {
+                log::info!("Check '{}' is no longer suppressed.", self.name);
+            }
+            for alarm in self.alarms.iter_mut().flatten() {
+                alarm.set_suppressed(suppressed);
+            }
+            self.suppressed = suppressed;
+        }
+        suppressed
+    }
 }
 
 #[async_trait]
@@ -105,6 +166,13 @@ where
     U: Alarm<Item = T::Item>,
 {
     async fn trigger(&mut self) {
+        if self.update_suppressed() {
+            // suppressed checks don't get data, but that doesn't make it stale
+            if let Some(last_data) = &self.last_data {
+                last_data.update_all();
+            }
+            return;
+        }
         let mut placeholders = crate::global_placeholders();
         crate::merge_placeholders(&mut placeholders, &self.placeholders);
         placeholders.insert(String::from("check_name"), self.name.clone());
@@ -140,6 +208,9 @@ where
                 .collect();
         }
         for ((i, data), alarms) in data_vec.iter().enumerate().zip(self.alarms.iter_mut()) {
+            if let (Ok(Some(_)), Some(last_data)) = (data, &self.last_data) {
+                last_data.update(i);
+            }
             match data {
                 Ok(data) => match data {
                     Some(data) => log::debug!(
@@ -157,8 +228,11 @@ where
                     err
                 ),
             }
+            let mut id_placeholders = placeholders.clone();
+            self.data_source
+                .add_id_placeholders(i, &mut id_placeholders);
             for alarm in alarms.iter_mut() {
-                let mut placeholders = placeholders.clone();
+                let mut placeholders = id_placeholders.clone();
                 let result = match data {
                     Ok(data) => match data {
                         Some(data) => alarm.put_data(data, placeholders).await,
@@ -176,6 +250,12 @@ where
         }
     }
 
+    fn start(&mut self) -> Result<()> {
+        self.data_source
+            .start()
+            .map_err(|x| Error(format!("Check '{}' failed to start: {x}", self.name)))
+    }
+
     fn interval(&self) -> std::time::Duration {
         self.interval
     }
@@ -185,13 +265,23 @@ where
     }
 }
 
-fn factory<'a, T, U>(check_config: &'a config::Check, actions: &ActionMap) -> Result<Box<dyn Check>>
+fn factory<'a, T, U>(
+    check_config: &'a config::Check,
+    actions: &ActionMap,
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+    acknowledgements: &std::sync::Arc<alarm::Acknowledgements>,
+    inboxes: &std::sync::Arc<ingestion::Inboxes>,
+) -> Result<Vec<Box<dyn Check>>>
 where
     T: DataSource + TryFrom<&'a config::Check, Error = Error> + 'static,
     U: DataSink<Item = T::Item> + TryFrom<&'a config::Alarm, Error = Error> + 'static,
     T::Item: filter::FilterFactory,
 {
     let data_source = T::try_from(check_config)?;
+    if let Some(inbox) = data_source.inbox() {
+        inboxes.register(&check_config.name, inbox)?;
+    }
     let mut all_alarms: Vec<Vec<AlarmBase<U>>> = Vec::new();
     for (i, id) in data_source.ids().iter().enumerate() {
         let mut alarms: Vec<AlarmBase<U>> = Vec::new();
@@ -221,11 +311,21 @@ where
                 );
             }
             let data_sink = U::try_from(alarm_config)?;
+            let alarm_key = alarm::AlarmKey {
+                check: check_config.name.clone(),
+                alarm: alarm_config.name.clone(),
+                id: id.clone(),
+            };
             let alarm_state_machine = alarm::StateMachine::new(
                 alarm_config.cycles,
                 alarm_config.repeat_cycles,
                 alarm_config.recover_cycles,
                 alarm_config.error_repeat_cycles,
+                alarm_config
+                    .escalation
+                    .iter()
+                    .map(|x| std::time::Duration::from_secs(x.after.into()))
+                    .collect(),
                 alarm_log_id.clone(),
             )?;
             let alarm = alarm::AlarmBase::new(
@@ -253,9 +353,37 @@ where
                     None => None,
                 },
                 alarm_config.error_recover_placeholders.clone(),
+                alarm_config
+                    .escalation
+                    .iter()
+                    .map(|x| {
+                        Ok((
+                            action::get_action(&x.action, actions)?,
+                            x.placeholders.clone(),
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
                 alarm_config.invert,
                 alarm_state_machine,
                 data_sink,
+                Some(alarm::StatePublisher::new(
+                    state_registry.clone(),
+                    alarm_key.clone(),
+                )),
+                Some(alarm::Silencer::new(
+                    silences.clone(),
+                    &[
+                        check_config.maintenance_windows.as_slice(),
+                        alarm_config.maintenance_windows.as_slice(),
+                    ]
+                    .concat(),
+                    alarm_key.clone(),
+                )?),
+                Some(alarm::Acknowledger::new(
+                    acknowledgements.clone(),
+                    alarm_key,
+                )),
+                alarm_config.silence_summary,
                 alarm_log_id,
             )?;
             alarms.push(alarm);
@@ -271,7 +399,23 @@ where
                 .collect()
         })
         .transpose()?;
-    Ok(Box::new(CheckBase::new(
+    let last_data = check_config
+        .staleness
+        .as_ref()
+        .map(|_| std::sync::Arc::new(staleness::LastData::new(data_source.ids().len())));
+    let staleness = match (&check_config.staleness, &last_data) {
+        (Some(staleness_config), Some(last_data)) => Some(staleness::from_staleness_config(
+            staleness_config,
+            check_config,
+            data_source.ids(),
+            last_data.clone(),
+            actions,
+            state_registry,
+            silences,
+        )?),
+        _ => None,
+    };
+    let check: Box<dyn Check> = Box::new(CheckBase::new(
         std::time::Duration::from_secs(check_config.interval.into()),
         check_config.name.clone(),
         check_config
@@ -281,51 +425,204 @@ where
         filter,
         data_source,
         all_alarms,
-    )?))
+        (!check_config.depends_on.is_empty()).then(|| {
+            alarm::Dependencies::new(
+                state_registry.clone(),
+                check_config
+                    .depends_on
+                    .iter()
+                    .map(|x| (x.check.clone(), x.alarm.clone()))
+                    .collect(),
+            )
+        }),
+        last_data,
+    )?);
+    Ok(std::iter::once(check).chain(staleness).collect())
 }
 
 pub fn from_check_config(
     check_config: &config::Check,
     actions: &ActionMap,
-) -> Result<Box<dyn Check>> {
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+    acknowledgements: &std::sync::Arc<alarm::Acknowledgements>,
+    inboxes: &std::sync::Arc<ingestion::Inboxes>,
+) -> Result<Vec<Box<dyn Check>>> {
     match &check_config.type_ {
         // NOTE Add mapping here when implementing new data source / alarms.
         #[cfg(feature = "docker")]
-        config::CheckType::DockerContainerStatus(_) => factory::<
-            docker_container_status::DockerContainerStatus,
-            alarm::BinaryState,
-        >(check_config, actions),
+        config::CheckType::DockerContainerStatus(_) => {
+            factory::<docker_container_status::DockerContainerStatus, alarm::BinaryState>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
+        }
         config::CheckType::FilesystemUsage(_) => {
-            factory::<filesystem_usage::FilesystemUsage, alarm::Level>(check_config, actions)
+            factory::<filesystem_usage::FilesystemUsage, alarm::Level>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
         }
-        config::CheckType::MemoryUsage(_) => {
-            factory::<memory_usage::MemoryUsage, alarm::Level>(check_config, actions)
+        config::CheckType::ExternalValue(external_value) => {
+            use external_value::ExternalValue;
+            match external_value.value_type {
+                config::ExternalValueType::BinaryState => {
+                    factory::<ExternalValue<measurement::BinaryState>, alarm::BinaryState>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+                config::ExternalValueType::DataSize => {
+                    factory::<ExternalValue<measurement::DataSize>, alarm::DataSize>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+                config::ExternalValueType::Integer => {
+                    factory::<ExternalValue<measurement::Integer>, alarm::Integer>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+                config::ExternalValueType::Level => {
+                    factory::<ExternalValue<measurement::Level>, alarm::Level>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+                config::ExternalValueType::StatusCode => {
+                    factory::<ExternalValue<measurement::StatusCode>, alarm::StatusCode>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+                #[cfg(feature = "sensors")]
+                config::ExternalValueType::Temperature => {
+                    factory::<ExternalValue<measurement::Temperature>, alarm::Temperature>(
+                        check_config,
+                        actions,
+                        state_registry,
+                        silences,
+                        acknowledgements,
+                        inboxes,
+                    )
+                }
+            }
         }
+        config::CheckType::Heartbeat(_) => factory::<heartbeat::Heartbeat, alarm::Integer>(
+            check_config,
+            actions,
+            state_registry,
+            silences,
+            acknowledgements,
+            inboxes,
+        ),
+        config::CheckType::MemoryUsage(_) => factory::<memory_usage::MemoryUsage, alarm::Level>(
+            check_config,
+            actions,
+            state_registry,
+            silences,
+            acknowledgements,
+            inboxes,
+        ),
         config::CheckType::NetworkThroughput(_) => {
-            factory::<network_throughput::NetworkThroughput, alarm::DataSize>(check_config, actions)
+            factory::<network_throughput::NetworkThroughput, alarm::DataSize>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
         }
         config::CheckType::PressureAverage(_) => {
-            factory::<pressure_average::PressureAverage, alarm::Level>(check_config, actions)
+            factory::<pressure_average::PressureAverage, alarm::Level>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
+        }
+        config::CheckType::ProcessExitStatus(_) => {
+            factory::<process_exit_status::ProcessExitStatus, alarm::StatusCode>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
+        }
+        config::CheckType::ProcessOutputInteger(_) => {
+            factory::<process_output_integer::ProcessOutputInteger, alarm::Integer>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
+        }
+        config::CheckType::ProcessOutputMatch(_) => {
+            factory::<process_output_match::ProcessOutputMatch, alarm::BinaryState>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
+        }
+        config::CheckType::SystemdUnitStatus(_) => {
+            factory::<systemd_unit_status::SystemdUnitStatus, alarm::BinaryState>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
         }
-        config::CheckType::ProcessExitStatus(_) => factory::<
-            process_exit_status::ProcessExitStatus,
-            alarm::StatusCode,
-        >(check_config, actions),
-        config::CheckType::ProcessOutputInteger(_) => factory::<
-            process_output_integer::ProcessOutputInteger,
-            alarm::Integer,
-        >(check_config, actions),
-        config::CheckType::ProcessOutputMatch(_) => factory::<
-            process_output_match::ProcessOutputMatch,
-            alarm::BinaryState,
-        >(check_config, actions),
-        config::CheckType::SystemdUnitStatus(_) => factory::<
-            systemd_unit_status::SystemdUnitStatus,
-            alarm::BinaryState,
-        >(check_config, actions),
         #[cfg(feature = "sensors")]
         config::CheckType::Temperature(_) => {
-            factory::<temperature::Temperature, alarm::Temperature>(check_config, actions)
+            factory::<temperature::Temperature, alarm::Temperature>(
+                check_config,
+                actions,
+                state_registry,
+                silences,
+                acknowledgements,
+                inboxes,
+            )
         }
     }
     .map_err(|x| {
@@ -0,0 +1,484 @@
+use crate::action;
+use crate::alarm::{self, AlarmState, StateHandler};
+use crate::check;
+use crate::config;
+use crate::ActionMap;
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+pub struct CompositeAlarm<T = alarm::StateMachine>
+where
+    T: StateHandler,
+{
+    name: String,
+    interval: std::time::Duration,
+    operator: config::CompositeOperator,
+    members: Vec<Member>,
+    state_registry: std::sync::Arc<alarm::StateRegistry>,
+    action: std::sync::Arc<dyn action::Action>,
+    placeholders: PlaceholderMap,
+    recover_action: Option<std::sync::Arc<dyn action::Action>>,
+    recover_placeholders: PlaceholderMap,
+    state_machine: T,
+    silencer: Option<alarm::Silencer>,
+    silenced: bool,
+    log_id: String,
+}
+
+struct Member {
+    check: String,
+    alarm: String,
+    id: Option<String>,
+}
+
+// Alarms matched by more than one member are only returned once.
+fn matching_alarms(
+    state_registry: &alarm::StateRegistry,
+    members: &[Member],
+) -> Result<Vec<(alarm::AlarmKey, AlarmState)>> {
+    let mut res = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for member in members.iter() {
+        let matching = state_registry.matching(
+            &member.check,
+            Some(member.alarm.as_str()),
+            member.id.as_deref(),
+        );
+        if matching.is_empty() {
+            return Err(Error(format!(
+                "Alarm '{}' of check '{}'{} not found.",
+                member.alarm,
+                member.check,
+                member
+                    .id
+                    .as_ref()
+                    .map(|x| format!(" with id '{x}'"))
+                    .unwrap_or_default()
+            )));
+        }
+        for (key, state) in matching {
+            if seen.insert(key.clone()) {
+                res.push((key, state));
+            }
+        }
+    }
+    Ok(res)
+}
+
+impl<T> CompositeAlarm<T>
+where
+    T: StateHandler,
+{
+    fn new(
+        name: String,
+        interval: std::time::Duration,
+        operator: config::CompositeOperator,
+        members: Vec<Member>,
+        state_registry: std::sync::Arc<alarm::StateRegistry>,
+        action: std::sync::Arc<dyn action::Action>,
+        placeholders: PlaceholderMap,
+        recover_action: Option<std::sync::Arc<dyn action::Action>>,
+        recover_placeholders: PlaceholderMap,
+        state_machine: T,
+        silencer: Option<alarm::Silencer>,
+        log_id: String,
+    ) -> Result<Self> {
+        if name.is_empty() {
+            return Err(Error(String::from("'name' cannot be empty.")));
+        } else if interval.is_zero() {
+            return Err(Error(String::from("'interval' cannot be 0.")));
+        } else if members.is_empty() {
+            return Err(Error(String::from("'alarms' cannot be empty.")));
+        }
+        let num_alarms = matching_alarms(&state_registry, &members)?.len();
+        match operator {
+            config::CompositeOperator::AtLeast(0) => {
+                Err(Error(String::from("'AtLeast' cannot be 0.")))
+            }
+            config::CompositeOperator::AtLeast(n) if n as usize > num_alarms => Err(Error(
+                format!("'AtLeast' cannot be greater than the number of alarms ({num_alarms})."),
+            )),
+            _ => Ok(Self {
+                name,
+                interval,
+                operator,
+                members,
+                state_registry,
+                action,
+                placeholders,
+                recover_action,
+                recover_placeholders,
+                state_machine,
+                silencer,
+                silenced: false,
+                log_id,
+            }),
+        }
+    }
+
+    // Returns the number of alarms and the ones that are currently in the bad or error state.
+    fn evaluate(&self) -> (usize, Vec<alarm::AlarmKey>) {
+        // alarms cannot be removed from the registry, so the members still match
+        let alarms = matching_alarms(&self.state_registry, &self.members).unwrap_or_default();
+        let num_alarms = alarms.len();
+        let bad_alarms = alarms
+            .into_iter()
+            .filter(|(_, state)| matches!(state, AlarmState::Bad | AlarmState::Error))
+            .map(|(key, _)| key)
+            .collect();
+        (num_alarms, bad_alarms)
+    }
+
+    fn update_silenced(&mut self) {
+        let silenced = self.silencer.as_ref().is_some_and(|x| x.is_silenced());
+        if silenced && !self.silenced {
+            log::info!("{} is silenced.", self.log_id);
+        } else if !silenced && self.silenced {
+            log::info!("{} is no longer silenced.", self.log_id);
+        }
+        self.silenced = silenced;
+    }
+
+    fn is_silenced(&self) -> bool {
+        if self.silenced {
+            log::info!("{}: Not triggering action while silenced.", self.log_id);
+        }
+        self.silenced
+    }
+
+    fn is_bad(&self, num_alarms: usize, num_bad_alarms: usize) -> bool {
+        match self.operator {
+            config::CompositeOperator::All => num_bad_alarms == num_alarms,
+            config::CompositeOperator::Any => num_bad_alarms > 0,
+            config::CompositeOperator::AtLeast(n) => num_bad_alarms >= n as usize,
+        }
+    }
+
+    fn add_placeholders(&self, bad_alarms: &[alarm::AlarmKey], placeholders: &mut PlaceholderMap) {
+        placeholders.insert(String::from("composite_name"), self.name.clone());
+        placeholders.insert(
+            String::from("composite_alarms"),
+            bad_alarms
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        crate::merge_placeholders(placeholders, &self.placeholders);
+    }
+
+    async fn trigger_action(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
+        self.state_machine.add_placeholders(&mut placeholders);
+        self.action.trigger(placeholders).await
+    }
+
+    async fn trigger_recover(&self, mut placeholders: PlaceholderMap) -> Result<()> {
+        if self.is_silenced() {
+            return Ok(());
+        }
+        self.state_machine.add_placeholders(&mut placeholders);
+        crate::merge_placeholders(&mut placeholders, &self.recover_placeholders);
+        match &self.recover_action {
+            Some(action) => action.trigger(placeholders).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn evaluate_and_trigger(&mut self, mut placeholders: PlaceholderMap) {
+        let (num_alarms, bad_alarms) = self.evaluate();
+        self.update_silenced();
+        self.add_placeholders(&bad_alarms, &mut placeholders);
+        let result = if self.is_bad(num_alarms, bad_alarms.len()) {
+            log::warn!(
+                "{}: {} out of {} alarms are bad.",
+                self.log_id,
+                bad_alarms.len(),
+                num_alarms
+            );
+            let (trigger, _) = self.state_machine.bad();
+            if trigger {
+                self.trigger_action(placeholders).await
+            } else {
+                Ok(())
+            }
+        } else {
+            let (trigger_recover, _) = self.state_machine.good();
+            if trigger_recover {
+                self.trigger_recover(placeholders).await
+            } else {
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            log::error!("{} had an error: {}", self.log_id, err);
+        }
+    }
+}
+
+#[async_trait]
+impl<T> check::Check for CompositeAlarm<T>
+where
+    T: StateHandler,
+{
+    async fn trigger(&mut self) {
+        self.evaluate_and_trigger(crate::global_placeholders())
+            .await;
+    }
+
+    fn interval(&self) -> std::time::Duration {
+        self.interval
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+pub fn from_composite_alarm_config(
+    composite_config: &config::CompositeAlarm,
+    actions: &ActionMap,
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+) -> Result<Box<dyn check::Check>> {
+    let log_id = format!("Composite alarm '{}'", composite_config.name);
+    let state_machine = alarm::StateMachine::new(
+        composite_config.cycles,
+        composite_config.repeat_cycles,
+        composite_config.recover_cycles,
+        0,
+        vec![],
+        log_id.clone(),
+    )?;
+    Ok(Box::new(CompositeAlarm::new(
+        composite_config.name.clone(),
+        std::time::Duration::from_secs(composite_config.interval.into()),
+        composite_config.operator,
+        composite_config
+            .alarms
+            .iter()
+            .map(|x| Member {
+                check: x.check.clone(),
+                alarm: x.alarm.clone(),
+                id: x.id.clone(),
+            })
+            .collect(),
+        state_registry.clone(),
+        action::get_action(&composite_config.action, actions)?,
+        composite_config.placeholders.clone(),
+        match &composite_config.recover_action {
+            Some(action) => Some(action::get_action(action, actions)?),
+            None => None,
+        },
+        composite_config.recover_placeholders.clone(),
+        state_machine,
+        // can be silenced with the composite alarm's name as the check name
+        Some(alarm::Silencer::new(
+            silences.clone(),
+            &[],
+            alarm::AlarmKey {
+                check: composite_config.name.clone(),
+                alarm: String::new(),
+                id: String::new(),
+            },
+        )?),
+        log_id,
+    )?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockall::predicate::*;
+
+    fn key(id: &str) -> alarm::AlarmKey {
+        alarm::AlarmKey {
+            check: String::from("Check"),
+            alarm: String::from("Alarm"),
+            id: String::from(id),
+        }
+    }
+
+    fn member(id: Option<&str>) -> Member {
+        Member {
+            check: String::from("Check"),
+            alarm: String::from("Alarm"),
+            id: id.map(String::from),
+        }
+    }
+
+    fn times_action(times: usize) -> std::sync::Arc<dyn action::Action> {
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .times(times)
+            .returning(|_| Ok(()));
+        std::sync::Arc::new(mock_action)
+    }
+
+    fn composite(
+        operator: config::CompositeOperator,
+        members: Vec<Member>,
+        state_registry: std::sync::Arc<alarm::StateRegistry>,
+        action: std::sync::Arc<dyn action::Action>,
+    ) -> Result<CompositeAlarm> {
+        CompositeAlarm::new(
+            String::from("Name"),
+            std::time::Duration::from_secs(1),
+            operator,
+            members,
+            state_registry,
+            action,
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            alarm::StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap(),
+            None,
+            String::from(""),
+        )
+    }
+
+    #[test]
+    fn test_validation() {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        alarm::StatePublisher::new(state_registry.clone(), key("a"));
+        alarm::StatePublisher::new(state_registry.clone(), key("b"));
+        assert!(composite(
+            config::CompositeOperator::All,
+            vec![],
+            state_registry.clone(),
+            times_action(0)
+        )
+        .is_err());
+        assert!(composite(
+            config::CompositeOperator::All,
+            vec![member(Some("c"))],
+            state_registry.clone(),
+            times_action(0)
+        )
+        .is_err());
+        assert!(composite(
+            config::CompositeOperator::AtLeast(0),
+            vec![member(None)],
+            state_registry.clone(),
+            times_action(0)
+        )
+        .is_err());
+        assert!(composite(
+            config::CompositeOperator::AtLeast(3),
+            vec![member(None)],
+            state_registry.clone(),
+            times_action(0)
+        )
+        .is_err());
+        assert!(composite(
+            config::CompositeOperator::AtLeast(2),
+            vec![member(None)],
+            state_registry,
+            times_action(0)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_operators() {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        alarm::StatePublisher::new(state_registry.clone(), key("a")).publish(AlarmState::Bad);
+        alarm::StatePublisher::new(state_registry.clone(), key("b")).publish(AlarmState::Error);
+        alarm::StatePublisher::new(state_registry.clone(), key("c"));
+        for (operator, bad) in [
+            (config::CompositeOperator::All, false),
+            (config::CompositeOperator::Any, true),
+            (config::CompositeOperator::AtLeast(1), true),
+            (config::CompositeOperator::AtLeast(2), true),
+            (config::CompositeOperator::AtLeast(3), false),
+        ] {
+            let composite = composite(
+                operator,
+                // overlapping members count each alarm once
+                vec![member(None), member(Some("a"))],
+                state_registry.clone(),
+                times_action(0),
+            )
+            .unwrap();
+            let (num_alarms, bad_alarms) = composite.evaluate();
+            assert_eq!(num_alarms, 3);
+            assert_eq!(bad_alarms, vec![key("a"), key("b")]);
+            assert_eq!(composite.is_bad(num_alarms, bad_alarms.len()), bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trigger_action() {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        let publisher_a = alarm::StatePublisher::new(state_registry.clone(), key("a"));
+        let publisher_b = alarm::StatePublisher::new(state_registry.clone(), key("b"));
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                assert_eq!(placeholders.get("composite_name").unwrap(), "Name");
+                assert_eq!(
+                    placeholders.get("composite_alarms").unwrap(),
+                    "Check/Alarm[a], Check/Alarm[b]"
+                );
+                assert_eq!(placeholders.get("alarm_state").unwrap(), "Bad");
+                true
+            }))
+            .returning(|_| Ok(()));
+        let mut composite = composite(
+            config::CompositeOperator::All,
+            vec![member(Some("a")), member(Some("b"))],
+            state_registry,
+            std::sync::Arc::new(mock_action),
+        )
+        .unwrap();
+        publisher_a.publish(AlarmState::Bad);
+        composite.evaluate_and_trigger(PlaceholderMap::new()).await;
+        publisher_b.publish(AlarmState::Bad);
+        composite.evaluate_and_trigger(PlaceholderMap::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_silenced() {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        alarm::StatePublisher::new(state_registry.clone(), key("a")).publish(AlarmState::Bad);
+        let silences = std::sync::Arc::new(alarm::Silences::default());
+        silences.add(
+            String::from("Name"),
+            None,
+            None,
+            std::time::Duration::from_secs(60),
+        );
+        let mut composite = CompositeAlarm::new(
+            String::from("Name"),
+            std::time::Duration::from_secs(1),
+            config::CompositeOperator::Any,
+            vec![member(None)],
+            state_registry,
+            times_action(0),
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            alarm::StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap(),
+            Some(
+                alarm::Silencer::new(
+                    silences,
+                    &[],
+                    alarm::AlarmKey {
+                        check: String::from("Name"),
+                        alarm: String::new(),
+                        id: String::new(),
+                    },
+                )
+                .unwrap(),
+            ),
+            String::from(""),
+        )
+        .unwrap();
+        composite.evaluate_and_trigger(PlaceholderMap::new()).await;
+    }
+}
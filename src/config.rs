@@ -15,6 +15,8 @@ pub struct Config {
     pub actions: Vec<Action>,
     #[serde(default)]
     pub checks: Vec<Check>,
+    #[serde(default)]
+    pub composite_alarms: Vec<CompositeAlarm>,
 }
 
 #[derive(Deserialize)]
@@ -23,6 +25,14 @@ pub struct General {
     pub boot_delay: Option<u32>,
     pub start_delay: Option<u32>,
     pub env_var_prefix: String,
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    pub control_socket: Option<String>,
+    pub ack_listen: Option<String>,
+    pub ack_url: Option<String>,
+    pub outbox: Option<Outbox>,
+    pub ingestion_socket: Option<String>,
+    pub ingestion_socket_mode: u32,
+    pub strict_templates: bool,
 }
 
 impl Default for General {
@@ -31,10 +41,35 @@ impl Default for General {
             boot_delay: None,
             start_delay: None,
             env_var_prefix: default::env_var_prefix(),
+            maintenance_windows: Vec::new(),
+            control_socket: None,
+            ack_listen: None,
+            ack_url: None,
+            outbox: None,
+            ingestion_socket: None,
+            ingestion_socket_mode: default::ingestion_socket_mode(),
+            strict_templates: false,
         }
     }
 }
 
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    pub cron: String,
+    pub duration: u32,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Outbox {
+    pub path: String,
+    #[serde(default = "default::outbox_max_age")]
+    pub max_age: u32,
+    #[serde(default = "default::outbox_replay_interval")]
+    pub replay_interval: u32,
+}
+
 #[derive(Default, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Log {
@@ -147,38 +182,152 @@ pub struct Action {
     pub timeout: u32,
     #[serde(default)]
     pub placeholders: PlaceholderMap,
+    #[serde(default)]
+    pub retry: Option<ActionRetry>,
+    #[serde(default)]
+    pub fallback: Vec<String>,
+    #[serde(default)]
+    pub rate_limit: Option<ActionRateLimit>,
+    #[serde(default)]
+    pub dedup: Option<ActionDedup>,
+    #[serde(default)]
+    pub outbox: bool,
+    #[serde(default)]
+    pub template_syntax: TemplateSyntax,
     #[serde(flatten)]
     pub type_: ActionType,
 }
 
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum TemplateSyntax {
+    #[default]
+    Basic,
+    Extended,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum Escape {
+    #[default]
+    None,
+    Json,
+    Url,
+    Html,
+    Shell,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionRetry {
+    #[serde(default = "default::action_retry_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default::action_retry_initial_delay")]
+    pub initial_delay: u32,
+    #[serde(default = "default::action_retry_backoff_factor")]
+    pub backoff_factor: f64,
+    #[serde(default = "default::action_retry_max_delay")]
+    pub max_delay: u32,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(tag = "type")]
 pub enum ActionType {
+    ActionGroup(ActionActionGroup),
+    Digest(ActionDigest),
     #[cfg(feature = "smtp")]
     Email(ActionEmail),
+    #[cfg(feature = "http")]
+    Gotify(ActionGotify),
     Log(ActionLog),
+    #[cfg(feature = "http")]
+    Matrix(ActionMatrix),
+    #[cfg(feature = "mqtt")]
+    Mqtt(ActionMqtt),
+    #[cfg(feature = "http")]
+    Ntfy(ActionNtfy),
     Process(ActionProcess),
     #[cfg(feature = "http")]
+    Slack(ActionSlack),
+    #[cfg(feature = "http")]
+    Telegram(ActionTelegram),
+    #[cfg(feature = "http")]
     Webhook(ActionWebhook),
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionRateLimit {
+    pub count: u32,
+    pub period: u32,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionDedup {
+    pub key: String,
+    pub period: u32,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionActionGroup {
+    pub actions: Vec<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionDigest {
+    pub action: String,
+    pub window: u32,
+    pub item_template: String,
+    #[serde(default = "default::digest_separator")]
+    pub separator: String,
+    #[serde(default)]
+    pub send_first: bool,
+}
+
 #[cfg(feature = "smtp")]
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ActionEmail {
     pub from: String,
-    pub to: String,
+    pub to: EmailAddresses,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
     #[serde(default)]
     pub reply_to: Option<String>,
     pub subject: String,
     pub body: String,
+    #[serde(default)]
+    pub html_body: Option<String>,
     pub smtp_server: String,
     #[serde(default)]
     pub smtp_port: Option<u16>,
     #[serde(default)]
     pub smtp_security: SmtpSecurity,
-    pub username: String,
-    pub password: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[cfg(feature = "smtp")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum EmailAddresses {
+    One(String),
+    List(Vec<String>),
+}
+
+#[cfg(feature = "smtp")]
+impl EmailAddresses {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Self::One(address) => vec![address.clone()],
+            Self::List(addresses) => addresses.clone(),
+        }
+    }
 }
 
 #[cfg(feature = "smtp")]
@@ -217,6 +366,168 @@ pub struct ActionWebhook {
     pub headers: std::collections::HashMap<String, String>,
     #[serde(default)]
     pub body: String,
+    #[serde(default)]
+    pub url_escape: Escape,
+    #[serde(default)]
+    pub body_escape: Option<Escape>,
+    #[serde(default)]
+    pub basic_auth: Option<HttpBasicAuth>,
+    #[serde(default)]
+    pub bearer_token_file: Option<String>,
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    #[serde(default)]
+    pub client_cert_file: Option<String>,
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+    #[serde(default)]
+    pub insecure: bool,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub success_status: Vec<u16>,
+    #[serde(default)]
+    pub success_regex: Option<String>,
+    #[serde(default)]
+    pub success_json: Option<WebhookSuccessJson>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSuccessJson {
+    pub pointer: String,
+    pub value: toml::Value,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HttpBasicAuth {
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub password_file: Option<String>,
+}
+
+#[cfg(feature = "mqtt")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionMqtt {
+    pub broker: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    pub topic: String,
+    #[serde(default)]
+    pub payload: String,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub last_will: Option<MqttLastWill>,
+}
+
+#[cfg(feature = "mqtt")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MqttLastWill {
+    pub topic: String,
+    pub payload: String,
+    #[serde(default)]
+    pub online_payload: Option<String>,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum ChatPriority {
+    Low,
+    Default,
+    High,
+    Urgent,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionGotify {
+    pub server: String,
+    pub token: String,
+    #[serde(default)]
+    pub title: String,
+    pub message: String,
+    #[serde(default)]
+    pub priority: Option<ChatPriority>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionMatrix {
+    pub server: String,
+    pub token: String,
+    pub room: String,
+    #[serde(default)]
+    pub title: String,
+    pub message: String,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionNtfy {
+    #[serde(default = "default::ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    pub message: String,
+    #[serde(default)]
+    pub priority: Option<ChatPriority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionSlack {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    pub message: String,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionTelegram {
+    #[serde(default = "default::telegram_server")]
+    pub server: String,
+    pub token: String,
+    pub chat_id: String,
+    #[serde(default)]
+    pub title: String,
+    pub message: String,
+    #[serde(default)]
+    pub priority: Option<ChatPriority>,
 }
 
 #[cfg(feature = "http")]
@@ -244,18 +555,49 @@ pub struct Check {
     pub placeholders: PlaceholderMap,
     #[serde(default)]
     pub filter: Option<Filter>,
+    #[serde(default)]
+    pub depends_on: Vec<CheckDependency>,
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    #[serde(default)]
+    pub staleness: Option<CheckStaleness>,
     #[serde(flatten)]
     pub type_: CheckType,
     #[serde(default)]
     pub alarms: Vec<Alarm>,
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CheckDependency {
+    pub check: String,
+    #[serde(default)]
+    pub alarm: Option<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CheckStaleness {
+    pub timeout: u32,
+    pub action: String,
+    #[serde(default)]
+    pub placeholders: PlaceholderMap,
+    #[serde(default)]
+    pub repeat_cycles: u32,
+    #[serde(default)]
+    pub recover_action: Option<String>,
+    #[serde(default)]
+    pub recover_placeholders: PlaceholderMap,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(tag = "type")]
 pub enum CheckType {
     #[cfg(feature = "docker")]
     DockerContainerStatus(CheckDockerContainerStatus),
+    ExternalValue(CheckExternalValue),
     FilesystemUsage(CheckFilesystemUsage),
+    Heartbeat(CheckHeartbeat),
     MemoryUsage(CheckMemoryUsage),
     NetworkThroughput(CheckNetworkThroughput),
     PressureAverage(CheckPressureAverage),
@@ -276,12 +618,39 @@ pub struct CheckDockerContainerStatus {
     pub containers: Vec<String>,
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CheckExternalValue {
+    pub value_type: ExternalValueType,
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum ExternalValueType {
+    BinaryState,
+    DataSize,
+    Integer,
+    Level,
+    StatusCode,
+    #[cfg(feature = "sensors")]
+    Temperature,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct CheckFilesystemUsage {
     pub mountpoints: Vec<String>,
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CheckHeartbeat {
+    pub socket_path: String,
+    #[serde(default = "default::heartbeat_socket_mode")]
+    pub socket_mode: u32,
+    pub names: Vec<String>,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct CheckMemoryUsage {
@@ -546,15 +915,34 @@ pub struct Alarm {
     pub error_recover_placeholders: PlaceholderMap,
     #[serde(default)]
     pub invert: bool,
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    #[serde(default)]
+    pub silence_summary: bool,
+    #[serde(default)]
+    pub escalation: Vec<AlarmEscalation>,
     #[serde(flatten)]
     pub type_: AlarmType,
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AlarmEscalation {
+    pub after: u32,
+    pub action: String,
+    #[serde(default)]
+    pub placeholders: PlaceholderMap,
+}
+
+// NOTE `Default` needs to be the first variant because some of the others only have optional
+// fields and would also match an empty alarm config.
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(untagged)]
 pub enum AlarmType {
-    DataSize(AlarmDataSize),
     Default(AlarmDefault),
+    Anomaly(AlarmAnomaly),
+    DataSize(AlarmDataSize),
+    Forecast(AlarmForecast),
     Integer(AlarmInteger),
     StatusCode(AlarmStatusCode),
     Level(AlarmLevel),
@@ -562,14 +950,50 @@ pub enum AlarmType {
     Temperature(AlarmTemperature),
 }
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AlarmAnomaly {
+    pub window_size: u16,
+    pub deviation: f64,
+    #[serde(default)]
+    pub warm_up_cycles: Option<u32>,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AlarmDataSize {
+    #[serde(default)]
+    unit: DataSizeUnit,
+    #[serde(default)]
+    min_data_size: Option<u64>,
+    #[serde(default, alias = "data_size")]
+    max_data_size: Option<u64>,
+}
+
+impl AlarmDataSize {
+    pub fn min_bytes(&self) -> Option<u64> {
+        self.min_data_size.map(|x| self.unit.bytes(x))
+    }
+
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_data_size.map(|x| self.unit.bytes(x))
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DataSize {
     #[serde(default)]
     unit: DataSizeUnit,
     data_size: u64,
 }
 
+impl DataSize {
+    pub fn bytes(&self) -> u64 {
+        self.unit.bytes(self.data_size)
+    }
+}
+
 #[derive(Deserialize, PartialEq, Default, Debug)]
 #[serde(deny_unknown_fields)]
 pub enum DataSizeUnit {
@@ -583,16 +1007,16 @@ pub enum DataSizeUnit {
     Gibibyte,
 }
 
-impl AlarmDataSize {
-    pub fn bytes(&self) -> u64 {
-        match self.unit {
-            DataSizeUnit::Byte => self.data_size,
-            DataSizeUnit::Kilobyte => self.data_size * 1000,
-            DataSizeUnit::Megabyte => self.data_size * 1000 * 1000,
-            DataSizeUnit::Gigabyte => self.data_size * 1000 * 1000 * 1000,
-            DataSizeUnit::Kibibyte => self.data_size * 1024,
-            DataSizeUnit::Mebibyte => self.data_size * 1024 * 1024,
-            DataSizeUnit::Gibibyte => self.data_size * 1024 * 1024 * 1024,
+impl DataSizeUnit {
+    pub fn bytes(&self, data_size: u64) -> u64 {
+        match self {
+            DataSizeUnit::Byte => data_size,
+            DataSizeUnit::Kilobyte => data_size * 1000,
+            DataSizeUnit::Megabyte => data_size * 1000 * 1000,
+            DataSizeUnit::Gigabyte => data_size * 1000 * 1000 * 1000,
+            DataSizeUnit::Kibibyte => data_size * 1024,
+            DataSizeUnit::Mebibyte => data_size * 1024 * 1024,
+            DataSizeUnit::Gibibyte => data_size * 1024 * 1024 * 1024,
         }
     }
 }
@@ -603,6 +1027,15 @@ impl AlarmDataSize {
 #[serde(deny_unknown_fields)]
 pub struct AlarmDefault {}
 
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AlarmForecast {
+    pub time_to_full: u32,
+    pub window_size: u16,
+    #[serde(default)]
+    pub capacity: Option<DataSize>,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AlarmInteger {
@@ -615,7 +1048,10 @@ pub struct AlarmInteger {
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AlarmLevel {
-    pub level: u8,
+    #[serde(default)]
+    pub min_level: Option<u8>,
+    #[serde(default, alias = "level")]
+    pub max_level: Option<u8>,
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
@@ -628,7 +1064,53 @@ pub struct AlarmStatusCode {
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AlarmTemperature {
-    pub temperature: i16,
+    #[serde(default)]
+    pub min_temperature: Option<i16>,
+    #[serde(default, alias = "temperature")]
+    pub max_temperature: Option<i16>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CompositeAlarm {
+    #[serde(default)]
+    pub disable: bool,
+    pub name: String,
+    #[serde(default = "default::check_interval")]
+    pub interval: u32,
+    #[serde(default)]
+    pub operator: CompositeOperator,
+    pub alarms: Vec<CompositeAlarmMember>,
+    pub action: String,
+    #[serde(default)]
+    pub placeholders: PlaceholderMap,
+    #[serde(default = "default::check_alarm_cycles")]
+    pub cycles: u32,
+    #[serde(default)]
+    pub repeat_cycles: u32,
+    #[serde(default)]
+    pub recover_action: Option<String>,
+    #[serde(default)]
+    pub recover_placeholders: PlaceholderMap,
+    #[serde(default = "default::check_alarm_recover_cycles")]
+    pub recover_cycles: u32,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum CompositeOperator {
+    #[default]
+    All,
+    Any,
+    AtLeast(u32),
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CompositeAlarmMember {
+    pub check: String,
+    pub alarm: String,
+    #[serde(default)]
+    pub id: Option<String>,
 }
 
 pub mod default {
@@ -647,6 +1129,40 @@ pub mod default {
         ACTION_TIMEOUT
     }
 
+    pub fn digest_separator() -> String {
+        String::from("\n")
+    }
+
+    pub const NTFY_SERVER: &str = "https://ntfy.sh";
+    pub fn ntfy_server() -> String {
+        NTFY_SERVER.into()
+    }
+
+    pub const TELEGRAM_SERVER: &str = "https://api.telegram.org";
+    pub fn telegram_server() -> String {
+        TELEGRAM_SERVER.into()
+    }
+
+    pub const ACTION_RETRY_MAX_ATTEMPTS: u32 = 3;
+    pub fn action_retry_max_attempts() -> u32 {
+        ACTION_RETRY_MAX_ATTEMPTS
+    }
+
+    pub const ACTION_RETRY_INITIAL_DELAY: u32 = 10;
+    pub fn action_retry_initial_delay() -> u32 {
+        ACTION_RETRY_INITIAL_DELAY
+    }
+
+    pub const ACTION_RETRY_BACKOFF_FACTOR: f64 = 2.0;
+    pub fn action_retry_backoff_factor() -> f64 {
+        ACTION_RETRY_BACKOFF_FACTOR
+    }
+
+    pub const ACTION_RETRY_MAX_DELAY: u32 = 600;
+    pub fn action_retry_max_delay() -> u32 {
+        ACTION_RETRY_MAX_DELAY
+    }
+
     pub const CHECK_INTERVAL: u32 = 300;
     pub fn check_interval() -> u32 {
         CHECK_INTERVAL
@@ -667,6 +1183,26 @@ pub mod default {
         CHECK_TIMEOUT
     }
 
+    pub const OUTBOX_MAX_AGE: u32 = 86400;
+    pub fn outbox_max_age() -> u32 {
+        OUTBOX_MAX_AGE
+    }
+
+    pub const OUTBOX_REPLAY_INTERVAL: u32 = 60;
+    pub fn outbox_replay_interval() -> u32 {
+        OUTBOX_REPLAY_INTERVAL
+    }
+
+    pub const INGESTION_SOCKET_MODE: u32 = 0o600;
+    pub fn ingestion_socket_mode() -> u32 {
+        INGESTION_SOCKET_MODE
+    }
+
+    pub const HEARTBEAT_SOCKET_MODE: u32 = 0o600;
+    pub fn heartbeat_socket_mode() -> u32 {
+        HEARTBEAT_SOCKET_MODE
+    }
+
     pub const DOCKER_SOCKET_PATH: &str = "/var/run/docker.sock";
     pub fn docker_socket_path() -> String {
         DOCKER_SOCKET_PATH.into()
@@ -784,6 +1320,7 @@ mod test {
         assert_eq!(config.report.events.len(), 0);
         assert_eq!(config.actions.len(), 0);
         assert_eq!(config.checks.len(), 0);
+        assert_eq!(config.composite_alarms.len(), 0);
     }
 
     #[test]
@@ -861,6 +1398,18 @@ mod test {
                     String::from("application/json")
                 )]),
                 body: String::from(r#"{"name": "{{ name }}"}"#),
+                url_escape: Escape::None,
+                body_escape: None,
+                basic_auth: None,
+                bearer_token_file: None,
+                ca_file: None,
+                client_cert_file: None,
+                client_key_file: None,
+                insecure: false,
+                proxy: None,
+                success_status: Vec::new(),
+                success_regex: None,
+                success_json: None,
             })
         );
 
@@ -879,11 +1428,107 @@ mod test {
         let alarm = check.alarms.first().unwrap();
         assert!(alarm.disable);
         assert_eq!(alarm.name, "test-alarm");
-        assert_eq!(alarm.type_, AlarmType::Level(AlarmLevel { level: 75 }));
+        assert_eq!(
+            alarm.type_,
+            AlarmType::Level(AlarmLevel {
+                min_level: None,
+                max_level: Some(75)
+            })
+        );
         assert_eq!(alarm.cycles, 3);
         assert_eq!(alarm.repeat_cycles, 600);
         assert_eq!(alarm.action, "test-action");
         assert_eq!(alarm.recover_cycles, 4);
         assert_eq!(alarm.recover_action, Some(String::from("test-action")));
     }
+
+    #[test]
+    fn test_alarm_forecast() {
+        let text = r#"
+            [[checks]]
+            name = "test-check"
+            type = "NetworkThroughput"
+            interfaces = ["eth0"]
+            received = true
+
+            [[checks.alarms]]
+            name = "test-alarm"
+            action = "test-action"
+            time_to_full = 86400
+            window_size = 12
+            capacity = {data_size = 10, unit = "Gibibyte"}
+        "#;
+        let config = Config::try_from(text).unwrap();
+        let alarm = config.checks.first().unwrap().alarms.first().unwrap();
+        match &alarm.type_ {
+            AlarmType::Forecast(forecast) => {
+                assert_eq!(forecast.time_to_full, 86400);
+                assert_eq!(forecast.window_size, 12);
+                assert_eq!(
+                    forecast.capacity.as_ref().unwrap().bytes(),
+                    10 * 1024 * 1024 * 1024
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_alarm_ranges() {
+        let text = r#"
+            [[checks]]
+            name = "test-check"
+            type = "NetworkThroughput"
+            interfaces = ["eth0"]
+            received = true
+
+            [[checks.alarms]]
+            name = "test-alarm-legacy"
+            action = "test-action"
+            data_size = 2
+            unit = "Kilobyte"
+
+            [[checks.alarms]]
+            name = "test-alarm"
+            action = "test-action"
+            min_data_size = 1
+            unit = "Kilobyte"
+        "#;
+        let config = Config::try_from(text).unwrap();
+        let alarms = &config.checks.first().unwrap().alarms;
+        match &alarms[0].type_ {
+            AlarmType::DataSize(data_size) => {
+                assert_eq!(data_size.min_bytes(), None);
+                assert_eq!(data_size.max_bytes(), Some(2000));
+            }
+            _ => panic!(),
+        }
+        match &alarms[1].type_ {
+            AlarmType::DataSize(data_size) => {
+                assert_eq!(data_size.min_bytes(), Some(1000));
+                assert_eq!(data_size.max_bytes(), None);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_composite_alarm() {
+        let text = r#"
+            [[composite_alarms]]
+            name = "test-composite"
+            action = "test-action"
+            operator = {AtLeast = 2}
+            alarms = [
+                {check = "test-check", alarm = "test-alarm"},
+                {check = "test-check", alarm = "test-alarm", id = "/home"},
+            ]
+        "#;
+        let config = Config::try_from(text).unwrap();
+        let composite = config.composite_alarms.first().unwrap();
+        assert_eq!(composite.operator, CompositeOperator::AtLeast(2));
+        assert_eq!(composite.alarms.len(), 2);
+        assert_eq!(composite.alarms[0].id, None);
+        assert_eq!(composite.alarms[1].id, Some(String::from("/home")));
+    }
 }
@@ -0,0 +1,558 @@
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use crate::alarm;
+use crate::ingestion;
+use crate::{Error, Result};
+
+const RESPONSE_ERROR_PREFIX: &str = "Error: ";
+
+const COMMAND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const COMMAND_MAX_SIZE: u64 = 4096;
+const ACK_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const ACK_REQUEST_MAX_SIZE: u64 = 8192;
+const ACCEPT_ERROR_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+const USAGE: &str = "Commands:
+  list
+  trigger <check>
+  report
+  silence <seconds> <check> [<alarm> [<id>]]
+  unsilence <check> [<alarm> [<id>]]
+  ack <check> [<alarm> [<id>]]
+  disable <check>
+  enable <check>";
+
+struct CheckControl {
+    trigger: tokio::sync::mpsc::Sender<()>,
+    enabled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+// Handle to the running checks and the report. Used by the control socket.
+pub struct Control {
+    state_registry: std::sync::Arc<alarm::StateRegistry>,
+    silences: std::sync::Arc<alarm::Silences>,
+    acknowledgements: std::sync::Arc<alarm::Acknowledgements>,
+    inboxes: std::sync::Arc<ingestion::Inboxes>,
+    checks: std::collections::BTreeMap<String, CheckControl>,
+    report: Option<tokio::sync::mpsc::Sender<()>>,
+}
+
+pub struct CheckReceiver {
+    pub trigger: tokio::sync::mpsc::Receiver<()>,
+    pub enabled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl CheckReceiver {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl Control {
+    pub(crate) fn new(
+        state_registry: std::sync::Arc<alarm::StateRegistry>,
+        silences: std::sync::Arc<alarm::Silences>,
+        acknowledgements: std::sync::Arc<alarm::Acknowledgements>,
+        inboxes: std::sync::Arc<ingestion::Inboxes>,
+    ) -> Self {
+        Self {
+            state_registry,
+            silences,
+            acknowledgements,
+            inboxes,
+            checks: std::collections::BTreeMap::new(),
+            report: None,
+        }
+    }
+
+    // A check with a `parent` shares the parent's enabled flag. The parent must be added first.
+    pub fn add_check(&mut self, name: &str, parent: Option<&str>) -> CheckReceiver {
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        let enabled = parent
+            .and_then(|x| self.checks.get(x))
+            .map(|x| x.enabled.clone())
+            .unwrap_or_else(|| std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true)));
+        self.checks.insert(
+            String::from(name),
+            CheckControl {
+                trigger: sender,
+                enabled: enabled.clone(),
+            },
+        );
+        CheckReceiver {
+            trigger: receiver,
+            enabled,
+        }
+    }
+
+    pub fn add_report(&mut self) -> tokio::sync::mpsc::Receiver<()> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        self.report = Some(sender);
+        receiver
+    }
+
+    pub fn acknowledgements(&self) -> std::sync::Arc<alarm::Acknowledgements> {
+        self.acknowledgements.clone()
+    }
+
+    pub fn inboxes(&self) -> std::sync::Arc<ingestion::Inboxes> {
+        self.inboxes.clone()
+    }
+
+    fn get_check(&self, name: &str) -> Result<&CheckControl> {
+        self.checks
+            .get(name)
+            .ok_or_else(|| Error(format!("Check '{name}' not found.")))
+    }
+
+    fn handle(&self, command: &str) -> Result<String> {
+        let args = split_arguments(command)?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["list"] => Ok(self.list()),
+            ["trigger", check] => {
+                // a full channel means a trigger is pending already
+                let _ = self.get_check(check)?.trigger.try_send(());
+                Ok(format!("Check '{check}' triggered."))
+            }
+            ["report"] => {
+                let report = self
+                    .report
+                    .as_ref()
+                    .ok_or_else(|| Error(String::from("Report is disabled.")))?;
+                let _ = report.try_send(());
+                Ok(String::from("Report triggered."))
+            }
+            ["silence", duration, check, rest @ ..] if rest.len() <= 2 => {
+                let duration: u64 = duration
+                    .parse()
+                    .map_err(|x| Error(format!("Invalid duration '{duration}': {x}")))?;
+                self.get_check(check)?;
+                self.silences.add(
+                    String::from(*check),
+                    rest.first().map(|x| String::from(*x)),
+                    rest.get(1).map(|x| String::from(*x)),
+                    std::time::Duration::from_secs(duration),
+                );
+                Ok(format!("Silenced for {duration} seconds."))
+            }
+            ["unsilence", check, rest @ ..] if rest.len() <= 2 => {
+                let removed =
+                    self.silences
+                        .remove(check, rest.first().copied(), rest.get(1).copied());
+                Ok(format!("Removed {removed} silence(s)."))
+            }
+            ["ack", check, rest @ ..] if rest.len() <= 2 => {
+                self.get_check(check)?;
+                let acknowledged = self.acknowledgements.acknowledge(
+                    check,
+                    rest.first().copied(),
+                    rest.get(1).copied(),
+                );
+                Ok(format!("Acknowledged {acknowledged} alarm(s)."))
+            }
+            [command @ ("disable" | "enable"), check] => {
+                let enable = *command == "enable";
+                self.get_check(check)?
+                    .enabled
+                    .store(enable, std::sync::atomic::Ordering::Relaxed);
+                Ok(format!("Check '{check}' {command}d."))
+            }
+            _ => Err(Error(format!("Invalid command.\n{USAGE}"))),
+        }
+    }
+
+    fn list(&self) -> String {
+        let mut lines = Vec::new();
+        for (name, check) in self.checks.iter() {
+            let enabled = check.enabled.load(std::sync::atomic::Ordering::Relaxed);
+            lines.push(format!(
+                "{name}{}",
+                if enabled { "" } else { " (disabled)" }
+            ));
+            for (key, state) in self.state_registry.matching(name, None, None) {
+                lines.push(format!("  {}[{}]: {}", key.alarm, key.id, state));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+// Quotes an argument of a command if it contains whitespace, so names with spaces can be used.
+pub fn quote_argument(argument: &str) -> String {
+    if !argument.is_empty()
+        && !argument
+            .chars()
+            .any(|x| x.is_whitespace() || x == '"' || x == '\\')
+    {
+        return String::from(argument);
+    }
+    let mut res = String::from("\"");
+    for c in argument.chars() {
+        if c == '"' || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    res
+}
+
+// Splits a command at whitespace. Arguments in double quotes may contain whitespace, `\"` and `\\`
+// within the quotes are escaped quotes and backslashes.
+fn split_arguments(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = command.chars().peekable();
+    loop {
+        while chars.next_if(|x| x.is_whitespace()).is_some() {}
+        let Some(c) = chars.next() else {
+            return Ok(args);
+        };
+        let mut arg = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c @ ('"' | '\\')) => arg.push(c),
+                        _ => return Err(Error(String::from("Invalid escape sequence."))),
+                    },
+                    Some(c) => arg.push(c),
+                    None => return Err(Error(String::from("Missing closing quote."))),
+                }
+            }
+            if chars.peek().is_some_and(|x| !x.is_whitespace()) {
+                return Err(Error(String::from(
+                    "Missing whitespace after closing quote.",
+                )));
+            }
+        } else {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|x| !x.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+pub async fn serve(control: Control, path: &std::path::Path) -> Result<()> {
+    // only the owner may access the socket
+    let listener = tokio::net::UnixListener::from_std(crate::bind_unix_socket(path, 0o600)?)
+        .map_err(|x| Error(x.to_string()))?;
+    log::info!("Control socket listening on {}.", path.display());
+    let control = std::sync::Arc::new(control);
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::error!("Control socket could not accept connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(&control, stream).await {
+                log::warn!("Control socket connection failed: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(control: &Control, stream: tokio::net::UnixStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut command = String::new();
+    tokio::time::timeout(
+        COMMAND_TIMEOUT,
+        tokio::io::BufReader::new(reader.take(COMMAND_MAX_SIZE)).read_line(&mut command),
+    )
+    .await
+    .map_err(|_| Error(String::from("Timed out waiting for the command.")))?
+    .map_err(|x| Error(x.to_string()))?;
+    log::info!("Control socket received command '{}'.", command.trim());
+    let response = control
+        .handle(&command)
+        .unwrap_or_else(|x| format!("{RESPONSE_ERROR_PREFIX}{x}"));
+    writer
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|x| Error(x.to_string()))
+}
+
+// Minimal HTTP endpoint for the `ack_url` placeholder: `GET /ack/<token>` shows a confirmation
+// page, `POST /ack/<token>` acknowledges the alarm. Link previews and scanners only send GET
+// requests, so they cannot acknowledge alarms by accident.
+pub async fn serve_ack(
+    acknowledgements: std::sync::Arc<alarm::Acknowledgements>,
+    address: &str,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|x| Error(format!("Could not bind to {address}: {x}")))?;
+    log::info!("Acknowledgement endpoint listening on {address}.");
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::error!("Acknowledgement endpoint could not accept connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        let acknowledgements = acknowledgements.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(
+                ACK_REQUEST_TIMEOUT,
+                handle_ack_connection(&acknowledgements, stream),
+            )
+            .await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => log::warn!("Acknowledgement connection failed: {err}"),
+                Err(_) => log::warn!("Acknowledgement connection timed out."),
+            }
+        });
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn handle_ack_request(acknowledgements: &alarm::Acknowledgements, request_line: &str) -> String {
+    let (status, content_type, body) = match request_line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [method @ ("GET" | "POST"), path, _] => {
+            let token = path.strip_prefix("/ack/");
+            let key = match *method {
+                "GET" => token.and_then(|x| acknowledgements.token_key(x)),
+                _ => token.and_then(|x| acknowledgements.acknowledge_token(x)),
+            };
+            match (*method, key) {
+                ("GET", Some(key)) => (
+                    "200 OK",
+                    "text/html",
+                    format!(
+                        "<!DOCTYPE html>\n<html><body><form method=\"post\">Acknowledge alarm {}? <button type=\"submit\">Acknowledge</button></form></body></html>\n",
+                        escape_html(&key.to_string())
+                    ),
+                ),
+                (_, Some(key)) => (
+                    "200 OK",
+                    "text/plain",
+                    format!("Alarm {key} acknowledged.\n"),
+                ),
+                (_, None) => (
+                    "404 Not Found",
+                    "text/plain",
+                    String::from("Unknown or expired token.\n"),
+                ),
+            }
+        }
+        _ => (
+            "400 Bad Request",
+            "text/plain",
+            String::from("Bad request.\n"),
+        ),
+    };
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+async fn handle_ack_connection(
+    acknowledgements: &alarm::Acknowledgements,
+    stream: tokio::net::TcpStream,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    // limit the size of the request, the body is not needed
+    let mut lines = tokio::io::BufReader::new(reader.take(ACK_REQUEST_MAX_SIZE)).lines();
+    let request_line = lines
+        .next_line()
+        .await
+        .map_err(|x| Error(x.to_string()))?
+        .unwrap_or_default();
+    // skip the headers
+    while let Some(line) = lines.next_line().await.map_err(|x| Error(x.to_string()))? {
+        if line.is_empty() {
+            break;
+        }
+    }
+    writer
+        .write_all(handle_ack_request(acknowledgements, &request_line).as_bytes())
+        .await
+        .map_err(|x| Error(x.to_string()))
+}
+
+pub async fn request(path: &std::path::Path, command: &str) -> Result<String> {
+    let mut stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(|x| Error(format!("Could not connect to {}: {x}", path.display())))?;
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .await
+        .map_err(|x| Error(x.to_string()))?;
+    let mut response = String::new();
+    tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+        .await
+        .map_err(|x| Error(x.to_string()))?;
+    match response.strip_prefix(RESPONSE_ERROR_PREFIX) {
+        Some(error) => Err(Error(String::from(error))),
+        None => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn control() -> Control {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        alarm::StatePublisher::new(
+            state_registry.clone(),
+            alarm::AlarmKey {
+                check: String::from("Check"),
+                alarm: String::from("Alarm"),
+                id: String::from("/"),
+            },
+        )
+        .publish(alarm::AlarmState::Bad);
+        Control::new(
+            state_registry,
+            std::sync::Arc::new(alarm::Silences::default()),
+            std::sync::Arc::new(alarm::Acknowledgements::default()),
+            std::sync::Arc::new(ingestion::Inboxes::default()),
+        )
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut control = control();
+        let mut check = control.add_check("Check", None);
+        let child = control.add_check("Check (Child)", Some("Check"));
+        assert!(control.handle("report").is_err());
+        let mut report = control.add_report();
+        assert!(control.handle("report").is_ok());
+        assert!(report.try_recv().is_ok());
+        assert!(control.handle("trigger Foo").is_err());
+        assert!(control.handle("trigger Check").is_ok());
+        assert!(check.trigger.try_recv().is_ok());
+        assert!(control.handle("disable Check").is_ok());
+        assert!(!check.is_enabled());
+        assert!(!child.is_enabled());
+        assert_eq!(
+            control.handle("list").unwrap(),
+            "Check (disabled)\n  Alarm[/]: Bad\nCheck (Child) (disabled)"
+        );
+        assert!(control.handle("enable Check").is_ok());
+        assert!(check.is_enabled());
+        assert!(control.handle("silence foo Check").is_err());
+        assert!(control.handle("silence 60 Check Alarm / foo").is_err());
+        assert!(control.handle("silence 60 Check Alarm").is_ok());
+        assert_eq!(
+            control.handle("unsilence Check Alarm").unwrap(),
+            "Removed 1 silence(s)."
+        );
+        assert_eq!(
+            control.handle("ack Check").unwrap(),
+            "Acknowledged 0 alarm(s)."
+        );
+        assert!(control.handle("ack Foo").is_err());
+        assert!(control.handle("foo").is_err());
+    }
+
+    #[test]
+    fn test_quoted_names() {
+        let mut control = control();
+        let mut check = control.add_check("Cron jobs", None);
+        assert!(control.handle("trigger Cron jobs").is_err());
+        assert!(control.handle(r#"trigger "Cron jobs"#).is_err());
+        assert!(control.handle(r#"trigger "Cron jobs"x"#).is_err());
+        assert_eq!(
+            control.handle(r#"trigger "Cron jobs""#).unwrap(),
+            "Check 'Cron jobs' triggered."
+        );
+        assert!(check.trigger.try_recv().is_ok());
+        let command = ["disable", "Cron jobs"]
+            .iter()
+            .map(|x| quote_argument(x))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(control.handle(&command).is_ok());
+        assert!(!check.is_enabled());
+        for argument in ["foo", "a b", "", r#"a "b" \c"#] {
+            assert_eq!(
+                split_arguments(&format!("x {}", quote_argument(argument))).unwrap(),
+                vec![String::from("x"), String::from(argument)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_ack_request() {
+        let acknowledgements = std::sync::Arc::new(alarm::Acknowledgements::new(Some(
+            String::from("http://localhost"),
+        )));
+        let acknowledger = alarm::Acknowledger::new(
+            acknowledgements.clone(),
+            alarm::AlarmKey {
+                check: String::from("Check"),
+                alarm: String::from("Alarm"),
+                id: String::from("/"),
+            },
+        );
+        let url = acknowledger.episode().0.unwrap();
+        let path = url.strip_prefix("http://localhost").unwrap();
+        assert!(
+            handle_ack_request(&acknowledgements, "GET /ack/foo HTTP/1.1")
+                .starts_with("HTTP/1.1 404 ")
+        );
+        assert!(handle_ack_request(&acknowledgements, "foo").starts_with("HTTP/1.1 400 "));
+        assert!(
+            handle_ack_request(&acknowledgements, "POST /ack/foo HTTP/1.1")
+                .starts_with("HTTP/1.1 404 ")
+        );
+        // GET only shows the confirmation page
+        let response = handle_ack_request(&acknowledgements, &format!("GET {path} HTTP/1.1"));
+        assert!(response.starts_with("HTTP/1.1 200 "));
+        assert!(response.contains("<form method=\"post\">"));
+        assert!(response.contains("Check/Alarm[/]"));
+        assert!(!acknowledger.episode().1);
+        assert!(
+            handle_ack_request(&acknowledgements, &format!("POST {path} HTTP/1.1"))
+                .starts_with("HTTP/1.1 200 ")
+        );
+        assert!(acknowledger.episode().1);
+    }
+
+    #[tokio::test]
+    async fn test_socket() {
+        let path = std::env::temp_dir().join(format!("minmon-test-{}.sock", std::process::id()));
+        let mut control = control();
+        control.add_check("Check", None);
+        let server_path = path.clone();
+        tokio::spawn(async move { serve(control, &server_path).await });
+        while !path.exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(
+            request(&path, "list").await.unwrap(),
+            "Check\n  Alarm[/]: Bad"
+        );
+        assert!(request(&path, "trigger Foo").await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use crate::measurement::Measurement;
+use crate::{Error, Result};
+
+const ACCEPT_ERROR_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub trait Ingest: Send + Sync {
+    fn push(&self, id: &str, value: &str) -> Result<()>;
+}
+
+// Latest pushed value for each ID of an `ExternalValue` check.
+pub struct Inbox<T>
+where
+    T: Measurement,
+{
+    ids: Vec<String>,
+    values: std::sync::Mutex<Vec<Option<T>>>,
+}
+
+impl<T> Inbox<T>
+where
+    T: Measurement,
+{
+    pub fn new(ids: Vec<String>) -> Self {
+        Self {
+            values: std::sync::Mutex::new(vec![None; ids.len()]),
+            ids,
+        }
+    }
+
+    // Returns the values pushed since the last call.
+    pub fn take(&self) -> Vec<Option<T>> {
+        self.values
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|x| x.take())
+            .collect()
+    }
+}
+
+impl<T> Ingest for Inbox<T>
+where
+    T: Measurement + Send,
+    T::Data: std::str::FromStr,
+    <T::Data as std::str::FromStr>::Err: std::fmt::Display,
+{
+    fn push(&self, id: &str, value: &str) -> Result<()> {
+        let index = self
+            .ids
+            .iter()
+            .position(|x| x == id)
+            .ok_or_else(|| Error(format!("Unknown id '{id}'.")))?;
+        let value = T::new(
+            value
+                .parse()
+                .map_err(|x| Error(format!("Invalid value '{value}': {x}")))?,
+        )?;
+        self.values.lock().unwrap()[index] = Some(value);
+        Ok(())
+    }
+}
+
+// Inboxes of the `ExternalValue` checks by check name.
+#[derive(Default)]
+pub struct Inboxes {
+    inboxes: std::sync::Mutex<std::collections::HashMap<String, std::sync::Arc<dyn Ingest>>>,
+}
+
+impl Inboxes {
+    pub fn register(&self, check: &str, inbox: std::sync::Arc<dyn Ingest>) -> Result<()> {
+        match self.inboxes.lock().unwrap().entry(String::from(check)) {
+            std::collections::hash_map::Entry::Occupied(_) => Err(Error(format!(
+                "Found duplicate ExternalValue check name: {check}"
+            ))),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(inbox);
+                Ok(())
+            }
+        }
+    }
+
+    // Lines have the format `<check> <id> <value>`.
+    fn push_line(&self, line: &str) -> Result<()> {
+        let mut parts = line.split_whitespace();
+        let (Some(check), Some(id), Some(value), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error(String::from(
+                "Invalid format, expected '<check> <id> <value>'.",
+            )));
+        };
+        let inbox = self
+            .inboxes
+            .lock()
+            .unwrap()
+            .get(check)
+            .cloned()
+            .ok_or_else(|| Error(format!("Unknown ExternalValue check '{check}'.")))?;
+        inbox.push(id, value)
+    }
+}
+
+pub async fn serve(
+    inboxes: std::sync::Arc<Inboxes>,
+    path: &std::path::Path,
+    mode: u32,
+) -> Result<()> {
+    let listener = tokio::net::UnixListener::from_std(crate::bind_unix_socket(path, mode)?)
+        .map_err(|x| Error(x.to_string()))?;
+    log::info!("Ingestion socket listening on {}.", path.display());
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::error!("Ingestion socket could not accept connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        let inboxes = inboxes.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(&inboxes, stream).await {
+                log::warn!("Ingestion socket connection failed: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(inboxes: &Inboxes, stream: tokio::net::UnixStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await.map_err(|x| Error(x.to_string()))? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match inboxes.push_line(&line) {
+            Ok(()) => String::from("OK\n"),
+            Err(err) => {
+                log::warn!("Could not ingest '{line}': {err}");
+                format!("Error: {err}\n")
+            }
+        };
+        writer
+            .write_all(response.as_bytes())
+            .await
+            .map_err(|x| Error(x.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measurement;
+
+    #[test]
+    fn test_push_line() {
+        let inbox = std::sync::Arc::new(Inbox::<measurement::Level>::new(vec![
+            String::from("a"),
+            String::from("b"),
+        ]));
+        let inboxes = Inboxes::default();
+        inboxes.register("Check", inbox.clone()).unwrap();
+        assert!(inboxes.register("Check", inbox.clone()).is_err());
+        assert!(inboxes.push_line("Check a").is_err());
+        assert!(inboxes.push_line("Check a 1 2").is_err());
+        assert!(inboxes.push_line("Foo a 1").is_err());
+        assert!(inboxes.push_line("Check c 1").is_err());
+        assert!(inboxes.push_line("Check a foo").is_err());
+        assert!(inboxes.push_line("Check a 101").is_err());
+        assert!(inboxes.push_line("Check a 10").is_ok());
+        assert!(inboxes.push_line("Check a 20").is_ok());
+        assert_eq!(
+            inbox.take(),
+            vec![Some(measurement::Level::new(20).unwrap()), None]
+        );
+        assert_eq!(inbox.take(), vec![None, None]);
+    }
+}
@@ -1,16 +1,26 @@
 #![deny(warnings)]
-#![allow(clippy::too_many_arguments, clippy::large_enum_variant)]
+#![allow(
+    clippy::too_many_arguments,
+    clippy::large_enum_variant,
+    clippy::manual_checked_ops
+)]
 #[cfg(not(target_os = "linux"))]
 compile_error!("Only Linux is supported");
 
 mod action;
 mod alarm;
 mod check;
+mod composite;
 pub mod config;
+pub mod control;
 mod filter;
+pub mod ingestion;
 mod measurement;
+pub mod outbox;
 mod process;
 mod report;
+mod staleness;
+mod template;
 pub mod uptime;
 mod window_buffer;
 
@@ -101,7 +111,7 @@ fn duration_iso8601(duration: std::time::Duration) -> String {
     if remainder == 0 {
         return String::from("PT0S");
     }
-    let mut res = String::new();
+    let mut res = String::from("P");
     let days = remainder / SECONDS_PER_DAY;
     if days > 0 {
         res = format!("P{days}D");
@@ -130,20 +140,101 @@ fn duration_iso8601(duration: std::time::Duration) -> String {
     res
 }
 
-fn init_actions(config: &config::Config) -> Result<ActionMap> {
+// Depth-first search over a dependency graph that calls `finish` for every node after all of its
+// dependencies. A node that is visited again while still on the path closes a cycle.
+fn visit_dependencies<'a>(
+    roots: impl IntoIterator<Item = &'a str>,
+    dependencies: impl Fn(&'a str) -> Result<Vec<&'a str>>,
+    mut finish: impl FnMut(&'a str) -> Result<()>,
+    cycle_error: &str,
+) -> Result<()> {
+    fn visit<'a>(
+        name: &'a str,
+        dependencies: &dyn Fn(&'a str) -> Result<Vec<&'a str>>,
+        finish: &mut dyn FnMut(&'a str) -> Result<()>,
+        cycle_error: &str,
+        path: &mut Vec<&'a str>,
+        done: &mut std::collections::HashSet<&'a str>,
+    ) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(position) = path.iter().position(|x| *x == name) {
+            let mut cycle = path[position..].to_vec();
+            cycle.push(name);
+            return Err(Error(format!("{cycle_error}: {}", cycle.join(" -> "))));
+        }
+        path.push(name);
+        for dependency in dependencies(name)? {
+            visit(dependency, dependencies, finish, cycle_error, path, done)?;
+        }
+        path.pop();
+        finish(name)?;
+        done.insert(name);
+        Ok(())
+    }
+    let mut done = std::collections::HashSet::new();
+    for root in roots {
+        visit(
+            root,
+            &dependencies,
+            &mut finish,
+            cycle_error,
+            &mut Vec::new(),
+            &mut done,
+        )?;
+    }
+    Ok(())
+}
+
+fn init_actions(
+    config: &config::Config,
+    outbox: Option<&std::sync::Arc<outbox::Outbox>>,
+) -> Result<ActionMap> {
     log::info!("Initializing {} actions(s)..", config.actions.len());
-    let mut res = ActionMap::new();
+    let mut by_name = std::collections::HashMap::new();
     for action_config in config.actions.iter() {
-        if res.contains_key(&action_config.name) {
+        if by_name
+            .insert(action_config.name.as_str(), action_config)
+            .is_some()
+        {
             return Err(Error(format!(
                 "Found duplicate action name: {}",
                 action_config.name
             )));
         }
-        let action = action::from_action_config(action_config)?;
-        res.insert(action_config.name.clone(), action);
-        log::info!("Action '{}' initialized.", action_config.name);
     }
+    // referenced (fallback, group member and digest) actions are initialized first
+    let mut res = ActionMap::new();
+    visit_dependencies(
+        config.actions.iter().map(|x| x.name.as_str()),
+        |name| {
+            let action_config = by_name[name];
+            let mut references: Vec<&str> =
+                action_config.fallback.iter().map(String::as_str).collect();
+            match &action_config.type_ {
+                config::ActionType::ActionGroup(action_group) => {
+                    references.extend(action_group.actions.iter().map(String::as_str))
+                }
+                config::ActionType::Digest(digest) => references.push(&digest.action),
+                _ => {}
+            }
+            if let Some(reference) = references.iter().find(|x| !by_name.contains_key(*x)) {
+                return Err(Error(format!(
+                    "Action '{name}' references unknown action '{reference}'."
+                )));
+            }
+            Ok(references)
+        },
+        |name| {
+            let action_config = by_name[name];
+            let action = action::from_action_config(action_config, &res, outbox)?;
+            res.insert(action_config.name.clone(), action);
+            log::info!("Action '{}' initialized.", action_config.name);
+            Ok(())
+        },
+        "Found loop between actions",
+    )?;
     Ok(res)
 }
 
@@ -169,7 +260,75 @@ fn init_report(config: &config::Config, actions: &ActionMap) -> Result<Option<re
     Ok(Some(report))
 }
 
-fn init_checks(config: &config::Config, actions: &ActionMap) -> Result<Vec<Box<dyn check::Check>>> {
+// Makes sure all dependencies exist, can become bad at all and there are no cycles in the
+// dependency graph.
+fn validate_check_dependencies(checks: &[config::Check]) -> Result<()> {
+    let by_name: std::collections::HashMap<&str, &config::Check> =
+        checks.iter().map(|x| (x.name.as_str(), x)).collect();
+    for check_config in checks.iter() {
+        for dependency in check_config.depends_on.iter() {
+            let parent = by_name.get(dependency.check.as_str()).ok_or_else(|| {
+                Error(format!(
+                    "Check '{}' depends on unknown check '{}'.",
+                    check_config.name, dependency.check
+                ))
+            })?;
+            if parent.disable {
+                return Err(Error(format!(
+                    "Check '{}' depends on disabled check '{}'.",
+                    check_config.name, dependency.check
+                )));
+            }
+            match &dependency.alarm {
+                Some(alarm) if alarm == staleness::ALARM_NAME && parent.staleness.is_some() => {}
+                Some(alarm) => match parent.alarms.iter().find(|x| &x.name == alarm) {
+                    None => {
+                        return Err(Error(format!(
+                            "Check '{}' depends on unknown alarm '{}' of check '{}'.",
+                            check_config.name, alarm, dependency.check
+                        )))
+                    }
+                    Some(x) if x.disable => {
+                        return Err(Error(format!(
+                            "Check '{}' depends on disabled alarm '{}' of check '{}'.",
+                            check_config.name, alarm, dependency.check
+                        )))
+                    }
+                    Some(_) => {}
+                },
+                None if parent.alarms.iter().all(|x| x.disable) && parent.staleness.is_none() => {
+                    return Err(Error(format!(
+                        "Check '{}' depends on check '{}' which has no enabled alarms.",
+                        check_config.name, dependency.check
+                    )))
+                }
+                None => {}
+            }
+        }
+    }
+    visit_dependencies(
+        checks.iter().map(|x| x.name.as_str()),
+        |name| {
+            Ok(by_name[name]
+                .depends_on
+                .iter()
+                .map(|x| x.check.as_str())
+                .collect())
+        },
+        |_| Ok(()),
+        "Found dependency cycle between checks",
+    )?;
+    Ok(())
+}
+
+fn init_checks(
+    config: &config::Config,
+    actions: &ActionMap,
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+    acknowledgements: &std::sync::Arc<alarm::Acknowledgements>,
+    inboxes: &std::sync::Arc<ingestion::Inboxes>,
+) -> Result<Vec<Box<dyn check::Check>>> {
     log::info!("Initializing {} check(s)..", config.checks.len());
     let mut res: Vec<Box<dyn check::Check>> = Vec::new();
     let mut used_names = std::collections::HashSet::new();
@@ -184,24 +343,141 @@ fn init_checks(config: &config::Config, actions: &ActionMap) -> Result<Vec<Box<d
             log::info!("Check '{}' is disabled.", check_config.name);
             continue;
         }
-        let check = check::from_check_config(check_config, actions)?;
+        if matches!(check_config.type_, config::CheckType::ExternalValue(_))
+            && config.general.ingestion_socket.is_none()
+        {
+            return Err(Error(format!(
+                "Check '{}' needs 'ingestion_socket' to be set in the general section.",
+                check_config.name
+            )));
+        }
+        for check in check::from_check_config(
+            check_config,
+            actions,
+            state_registry,
+            silences,
+            acknowledgements,
+            inboxes,
+        )? {
+            log::info!(
+                "Check '{}' will be triggered every {} seconds.",
+                check.name(),
+                check.interval().as_secs()
+            );
+            res.push(check);
+        }
+    }
+    validate_check_dependencies(&config.checks)?;
+    Ok(res)
+}
+
+// Needs to be called after `init_checks` because composite alarms reference the checks' alarms.
+fn init_composite_alarms(
+    config: &config::Config,
+    actions: &ActionMap,
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+) -> Result<Vec<Box<dyn check::Check>>> {
+    log::info!(
+        "Initializing {} composite alarm(s)..",
+        config.composite_alarms.len()
+    );
+    let mut res: Vec<Box<dyn check::Check>> = Vec::new();
+    let mut used_names = std::collections::HashSet::new();
+    for composite_config in config.composite_alarms.iter() {
+        if !used_names.insert(composite_config.name.clone()) {
+            return Err(Error(format!(
+                "Found duplicate composite alarm name: {}",
+                composite_config.name
+            )));
+        }
+        // composite alarms are silenced by their name like checks
+        if config
+            .checks
+            .iter()
+            .any(|x| x.name == composite_config.name)
+        {
+            return Err(Error(format!(
+                "Composite alarm name is already used by a check: {}",
+                composite_config.name
+            )));
+        }
+        if composite_config.disable {
+            log::info!("Composite alarm '{}' is disabled.", composite_config.name);
+            continue;
+        }
+        let composite = composite::from_composite_alarm_config(
+            composite_config,
+            actions,
+            state_registry,
+            silences,
+        )
+        .map_err(|x| {
+            Error(format!(
+                "Failed to create composite alarm '{}': {}",
+                composite_config.name, x
+            ))
+        })?;
         log::info!(
-            "Check '{}' will be triggered every {} seconds.",
-            check.name(),
-            check.interval().as_secs()
+            "Composite alarm '{}' will be evaluated every {} seconds.",
+            composite.name(),
+            composite.interval().as_secs()
         );
-        res.push(check);
+        res.push(composite);
     }
     Ok(res)
 }
 
-type ConfigState = (Option<report::Report>, Vec<Box<dyn check::Check>>);
+type ConfigState = (
+    ActionMap,
+    Option<report::Report>,
+    Vec<Box<dyn check::Check>>,
+    control::Control,
+    Option<std::sync::Arc<outbox::Outbox>>,
+);
 
 pub fn from_config(config: &config::Config) -> Result<ConfigState> {
-    let actions = init_actions(config)?;
+    if config.general.strict_templates {
+        template::validate(config)?;
+    }
+    let outbox = match &config.general.outbox {
+        Some(outbox) => Some(std::sync::Arc::new(
+            outbox::Outbox::try_from(outbox)
+                .map_err(|x| Error(format!("Failed to create outbox: {x}")))?,
+        )),
+        None => None,
+    };
+    let actions = init_actions(config, outbox.as_ref())?;
     let report = init_report(config, &actions)?;
-    let checks = init_checks(config, &actions)?;
-    Ok((report, checks))
+    let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+    let silences = std::sync::Arc::new(
+        alarm::Silences::new(&config.general.maintenance_windows)
+            .map_err(|x| Error(format!("Failed to create global maintenance window: {x}")))?,
+    );
+    let acknowledgements =
+        std::sync::Arc::new(alarm::Acknowledgements::new(config.general.ack_url.clone()));
+    let inboxes = std::sync::Arc::new(ingestion::Inboxes::default());
+    let mut checks = init_checks(
+        config,
+        &actions,
+        &state_registry,
+        &silences,
+        &acknowledgements,
+        &inboxes,
+    )?;
+    checks.extend(init_composite_alarms(
+        config,
+        &actions,
+        &state_registry,
+        &silences,
+    )?);
+    Ok((
+        actions,
+        report,
+        checks,
+        control::Control::new(state_registry, silences, acknowledgements, inboxes),
+        outbox,
+    ))
 }
 
 pub fn start_delay(config: &config::Config) -> Option<std::time::Duration> {
@@ -222,6 +498,35 @@ pub fn start_delay(config: &config::Config) -> Option<std::time::Duration> {
     })
 }
 
+// Binds a non-blocking Unix socket and sets its mode. A socket left over from a previous run is
+// removed first, any other file at `path` is an error.
+fn bind_unix_socket(path: &std::path::Path, mode: u32) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .map_err(|x| Error(format!("Could not remove {}: {x}", path.display())))?,
+        Ok(_) => {
+            return Err(Error(format!(
+                "{} exists and is not a socket.",
+                path.display()
+            )))
+        }
+        Err(_) => {}
+    }
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .map_err(|x| Error(format!("Could not bind to {}: {x}", path.display())))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(|x| {
+        Error(format!(
+            "Could not set permissions of {}: {x}",
+            path.display()
+        ))
+    })?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|x| Error(x.to_string()))?;
+    Ok(listener)
+}
+
 fn get_number<T>(error_message: &str, line: &str, column: usize) -> Result<T>
 where
     T: std::str::FromStr,
@@ -272,7 +577,9 @@ mod test {
         let duration = std::time::Duration::from_secs(123630);
         assert_eq!(duration_iso8601(duration), "P1DT10H20M30S");
         let duration = std::time::Duration::from_secs(37230);
-        assert_eq!(duration_iso8601(duration), "T10H20M30S");
+        assert_eq!(duration_iso8601(duration), "PT10H20M30S");
+        let duration = std::time::Duration::from_secs(172800);
+        assert_eq!(duration_iso8601(duration), "P2D");
         let duration = std::time::Duration::from_secs(0);
         assert_eq!(duration_iso8601(duration), "PT0S");
     }
@@ -284,4 +591,279 @@ mod test {
         assert_eq!(get_number::<u32>("error", line, 5).unwrap(), 5);
         assert!(get_number::<u32>("error", line, 6).is_err());
     }
+
+    #[test]
+    fn test_bind_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("minmon-bind-{}.sock", std::process::id()));
+        std::fs::write(&path, "foo").unwrap();
+        assert!(bind_unix_socket(&path, 0o600).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "foo");
+        std::fs::remove_file(&path).unwrap();
+        drop(bind_unix_socket(&path, 0o600).unwrap());
+        // the stale socket is replaced
+        drop(bind_unix_socket(&path, 0o660).unwrap());
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o660
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_check_dependencies() {
+        let checks = |depends_on: &str| {
+            config::Config::try_from(
+                format!(
+                    r#"
+                    [[checks]]
+                    name = "A"
+                    type = "MemoryUsage"
+                    depends_on = [{{check = "B"}}]
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Action"
+                    level = 90
+
+                    [[checks]]
+                    name = "B"
+                    type = "MemoryUsage"
+                    depends_on = [{depends_on}]
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Action"
+                    level = 90
+
+                    [[checks]]
+                    name = "C"
+                    type = "MemoryUsage"
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Action"
+                    level = 90
+
+                    [[checks.alarms]]
+                    name = "Disabled"
+                    disable = true
+                    action = "Action"
+                    level = 90
+
+                    [[checks]]
+                    name = "D"
+                    type = "MemoryUsage"
+
+                    [[checks]]
+                    name = "F"
+                    type = "MemoryUsage"
+                    staleness = {{timeout = 900, action = "Action"}}
+
+                    [[checks]]
+                    name = "E"
+                    disable = true
+                    type = "MemoryUsage"
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Action"
+                    level = 90
+                "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+            .checks
+        };
+        assert!(validate_check_dependencies(&checks("")).is_ok());
+        assert!(validate_check_dependencies(&checks(r#"{check = "C"}"#)).is_ok());
+        assert!(validate_check_dependencies(&checks(r#"{check = "C", alarm = "Alarm"}"#)).is_ok());
+        assert!(validate_check_dependencies(&checks(r#"{check = "F"}"#)).is_ok());
+        assert!(
+            validate_check_dependencies(&checks(r#"{check = "F", alarm = "Staleness"}"#)).is_ok()
+        );
+        assert!(
+            validate_check_dependencies(&checks(r#"{check = "C", alarm = "Staleness"}"#)).is_err()
+        );
+        assert!(validate_check_dependencies(&checks(r#"{check = "G"}"#)).is_err());
+        assert!(validate_check_dependencies(&checks(r#"{check = "C", alarm = "Foo"}"#)).is_err());
+        // these can never be bad
+        assert!(
+            validate_check_dependencies(&checks(r#"{check = "C", alarm = "Disabled"}"#)).is_err()
+        );
+        assert!(validate_check_dependencies(&checks(r#"{check = "D"}"#)).is_err());
+        assert!(validate_check_dependencies(&checks(r#"{check = "E"}"#)).is_err());
+        assert!(validate_check_dependencies(&checks(r#"{check = "B"}"#)).is_err());
+        assert!(validate_check_dependencies(&checks(r#"{check = "A"}"#)).is_err());
+    }
+
+    #[test]
+    fn test_init_composite_alarms() {
+        let config = |name: &str| {
+            config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "Action"
+                    type = "Log"
+                    template = "Action"
+
+                    [[checks]]
+                    name = "Check"
+                    type = "MemoryUsage"
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Action"
+                    level = 90
+
+                    [[composite_alarms]]
+                    name = "{name}"
+                    action = "Action"
+                    alarms = [{{check = "Check", alarm = "Alarm"}}]
+                "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        alarm::StatePublisher::new(
+            state_registry.clone(),
+            alarm::AlarmKey {
+                check: String::from("Check"),
+                alarm: String::from("Alarm"),
+                id: String::new(),
+            },
+        );
+        let init = |config: &config::Config| {
+            init_composite_alarms(
+                config,
+                &init_actions(config, None).unwrap(),
+                &state_registry,
+                &std::sync::Arc::new(alarm::Silences::default()),
+            )
+        };
+        assert_eq!(init(&config("Composite")).unwrap().len(), 1);
+        // both would be silenced by the same name
+        assert!(init(&config("Check")).is_err());
+    }
+
+    #[test]
+    fn test_init_actions() {
+        let config = |fallback: &str| {
+            config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "A"
+                    type = "Log"
+                    template = "A"
+                    fallback = ["B"]
+
+                    [[actions]]
+                    name = "B"
+                    type = "Log"
+                    template = "B"
+                    fallback = [{fallback}]
+
+                    [[actions]]
+                    name = "C"
+                    type = "Log"
+                    template = "C"
+
+                    [[actions]]
+                    name = "D"
+                    type = "ActionGroup"
+                    actions = ["A", "C"]
+                "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        assert_eq!(init_actions(&config(""), None).unwrap().len(), 4);
+        assert!(init_actions(&config(r#""C""#), None).is_ok());
+        assert!(init_actions(&config(r#""E""#), None).is_err());
+        assert!(init_actions(&config(r#""D""#), None).is_err());
+        assert!(init_actions(&config(r#""A""#), None).is_err());
+        assert!(init_actions(&config(r#""B""#), None).is_err());
+        // group members are initialized before the group, groups may be nested
+        let config = |actions: &str| {
+            config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "A"
+                    type = "ActionGroup"
+                    actions = ["B"]
+
+                    [[actions]]
+                    name = "B"
+                    type = "ActionGroup"
+                    actions = [{actions}]
+
+                    [[actions]]
+                    name = "C"
+                    type = "Log"
+                    template = "C"
+                "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        assert_eq!(init_actions(&config(r#""C""#), None).unwrap().len(), 3);
+        assert!(init_actions(&config(r#""D""#), None).is_err());
+        assert!(init_actions(&config(r#""C", "A""#), None).is_err());
+        // the digest target is initialized before the digest
+        let config = |action: &str| {
+            config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "A"
+                    type = "Digest"
+                    action = "{action}"
+                    window = 60
+                    item_template = "A"
+
+                    [[actions]]
+                    name = "B"
+                    type = "ActionGroup"
+                    actions = ["A"]
+
+                    [[actions]]
+                    name = "C"
+                    type = "Log"
+                    template = "C"
+                "#
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        assert_eq!(init_actions(&config("C"), None).unwrap().len(), 3);
+        assert!(init_actions(&config("D"), None).is_err());
+        assert!(init_actions(&config("B"), None).is_err());
+        assert!(init_actions(&config("A"), None).is_err());
+        // groups do not retry, their actions do
+        let config = config::Config::try_from(
+            r#"
+            [[actions]]
+            name = "A"
+            type = "Log"
+            template = "A"
+
+            [[actions]]
+            name = "B"
+            type = "ActionGroup"
+            actions = ["A"]
+            retry = {max_attempts = 3}
+            "#,
+        )
+        .unwrap();
+        assert!(init_actions(&config, None).is_err());
+    }
 }
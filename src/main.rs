@@ -47,14 +47,40 @@ fn init_logging(config: &config::Config) -> Result<()> {
     Ok(())
 }
 
-async fn random_interval(max: std::time::Duration) {
-    let delay = rand::random::<f32>() * max.as_secs_f32() + 0.001;
-    let mut delay = tokio::time::interval(std::time::Duration::from_secs_f32(delay));
-    delay.tick().await; // the first tick completes immediately
-    delay.tick().await;
+fn random_delay(max: std::time::Duration) -> std::time::Duration {
+    std::time::Duration::from_secs_f32(rand::random::<f32>() * max.as_secs_f32() + 0.001)
+}
+
+// Splits long sleep durations into smaller ones to compensate for clock drift and system
+// standby/hibernation.
+async fn sleep_until(datetime: chrono::DateTime<chrono::Utc>) {
+    let mut duration = datetime.signed_duration_since(chrono::Utc::now());
+    while duration > chrono::TimeDelta::minutes(10) {
+        tokio::time::sleep(std::time::Duration::from_secs(9 * 60)).await;
+        duration = datetime.signed_duration_since(chrono::Utc::now());
+    }
+    tokio::time::sleep(duration.to_std().unwrap_or_default()).await;
+}
+
+async fn control_client() -> Result<()> {
+    let mut args = std::env::args().skip(2);
+    let socket_path = args
+        .next()
+        .ok_or_else(|| Error(String::from("Control socket path not specified.")))?;
+    let command = args
+        .map(|x| minmon::control::quote_argument(&x))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let response = minmon::control::request(std::path::Path::new(&socket_path), &command).await?;
+    println!("{response}");
+    Ok(())
 }
 
 async fn main_wrapper() -> Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("ctl") {
+        return control_client().await;
+    }
+
     minmon::uptime::init()?;
 
     let config_path = get_config_path()?;
@@ -74,7 +100,7 @@ async fn main_wrapper() -> Result<()> {
 
     minmon::init_env_vars(&config);
 
-    let (report, checks) = minmon::from_config(&config)?;
+    let (actions, report, checks, mut control, outbox) = minmon::from_config(&config)?;
 
     if let Some(start_delay) = minmon::start_delay(&config) {
         log::info!(
@@ -84,38 +110,69 @@ async fn main_wrapper() -> Result<()> {
         tokio::time::sleep(start_delay).await;
     }
 
-    for mut check in checks {
+    if let Some(outbox) = outbox {
+        // the first tick replays the entries left over from the last run right away
+        let mut interval = tokio::time::interval(outbox.replay_interval());
         tokio::spawn(async move {
-            random_interval(check.interval()).await;
-            let mut interval = tokio::time::interval(check.interval());
             loop {
                 interval.tick().await;
-                check.trigger().await;
+                outbox.replay(None).await;
+            }
+        });
+    }
+
+    for action in actions.values() {
+        action.start()?;
+    }
+
+    for mut check in checks {
+        check.start()?;
+        let mut receiver = control.add_check(check.name(), check.parent());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(
+                tokio::time::Instant::now() + random_delay(check.interval()),
+                check.interval(),
+            );
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if receiver.is_enabled() {
+                            check.trigger().await;
+                        } else {
+                            log::debug!("Check '{}' is disabled.", check.name());
+                            check.disabled();
+                        }
+                    }
+                    Some(()) = receiver.trigger.recv() => check.trigger().await,
+                }
             }
         });
     }
 
     if let Some(mut report) = report {
+        let mut receiver = control.add_report();
         tokio::spawn(async move {
             match report.when.clone() {
                 minmon::ReportWhen::Interval(interval) => {
                     let mut interval = tokio::time::interval(interval);
                     loop {
-                        interval.tick().await;
-                        report.trigger().await;
+                        tokio::select! {
+                            _ = interval.tick() => report.trigger().await,
+                            Some(()) = receiver.recv() => report.trigger().await,
+                        }
                     }
                 }
                 minmon::ReportWhen::Cron(schedule) => {
                     report.trigger().await;
                     for datetime in schedule.upcoming(chrono::Utc) {
-                        // here we split long sleep durations into smaller ones to compensate for
-                        // clock drift and system standby/hibernation
-                        let mut duration = datetime.signed_duration_since(chrono::Utc::now());
-                        while duration > chrono::TimeDelta::minutes(10) {
-                            tokio::time::sleep(std::time::Duration::from_secs(9 * 60)).await;
-                            duration = datetime.signed_duration_since(chrono::Utc::now());
+                        let sleep = sleep_until(datetime);
+                        tokio::pin!(sleep);
+                        loop {
+                            tokio::select! {
+                                _ = &mut sleep => break,
+                                Some(()) = receiver.recv() => report.trigger().await,
+                            }
                         }
-                        tokio::time::sleep(duration.to_std().unwrap()).await;
                         report.trigger().await;
                     }
                 }
@@ -123,6 +180,37 @@ async fn main_wrapper() -> Result<()> {
         });
     }
 
+    if let Some(address) = config.general.ack_listen.clone() {
+        let acknowledgements = control.acknowledgements();
+        tokio::spawn(async move {
+            if let Err(err) = minmon::control::serve_ack(acknowledgements, &address).await {
+                log::error!("Acknowledgement endpoint failed: {err}");
+            }
+        });
+    }
+
+    if let Some(socket_path) = config.general.ingestion_socket.clone() {
+        let mode = config.general.ingestion_socket_mode;
+        let inboxes = control.inboxes();
+        tokio::spawn(async move {
+            if let Err(err) =
+                minmon::ingestion::serve(inboxes, std::path::Path::new(&socket_path), mode).await
+            {
+                log::error!("Ingestion socket failed: {err}");
+            }
+        });
+    }
+
+    if let Some(socket_path) = config.general.control_socket.clone() {
+        tokio::spawn(async move {
+            if let Err(err) =
+                minmon::control::serve(control, std::path::Path::new(&socket_path)).await
+            {
+                log::error!("Control socket failed: {err}");
+            }
+        });
+    }
+
     use tokio::signal::unix::{signal, SignalKind};
     let mut sigint = signal(SignalKind::interrupt()).unwrap();
     let mut sigterm = signal(SignalKind::interrupt()).unwrap();
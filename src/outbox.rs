@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::{Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+const FILE_EXTENSION: &str = "toml";
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    action: String,
+    // seconds since the UNIX epoch
+    created: u64,
+    placeholders: PlaceholderMap,
+}
+
+// Implemented by actions that use the outbox to deliver replayed entries.
+#[async_trait]
+pub(crate) trait Replay: Send + Sync {
+    async fn replay(&self, path: std::path::PathBuf, placeholders: PlaceholderMap);
+}
+
+// On-disk queue of action triggers. Entries are stored before an action is triggered and removed
+// after it was delivered. Entries that are left over are replayed.
+pub struct Outbox {
+    path: std::path::PathBuf,
+    max_age: std::time::Duration,
+    replay_interval: std::time::Duration,
+    counter: std::sync::atomic::AtomicU64,
+    // entries that are currently being delivered
+    in_flight: std::sync::Mutex<std::collections::HashSet<std::path::PathBuf>>,
+    actions: std::sync::Mutex<std::collections::HashMap<String, std::sync::Weak<dyn Replay>>>,
+}
+
+impl Outbox {
+    pub fn new(
+        path: std::path::PathBuf,
+        max_age: std::time::Duration,
+        replay_interval: std::time::Duration,
+    ) -> Result<Self> {
+        if max_age.is_zero() {
+            return Err(Error(String::from("'max_age' cannot be 0.")));
+        } else if replay_interval.is_zero() {
+            return Err(Error(String::from("'replay_interval' cannot be 0.")));
+        }
+        std::fs::create_dir_all(&path)
+            .map_err(|x| Error(format!("Could not create {}: {x}", path.display())))?;
+        Ok(Self {
+            path,
+            max_age,
+            replay_interval,
+            counter: std::sync::atomic::AtomicU64::new(0),
+            in_flight: std::sync::Mutex::new(std::collections::HashSet::new()),
+            actions: std::sync::Mutex::new(std::collections::HashMap::new()),
+        })
+    }
+
+    pub fn replay_interval(&self) -> std::time::Duration {
+        self.replay_interval
+    }
+
+    pub(crate) fn register(&self, action: &str, replay: std::sync::Weak<dyn Replay>) {
+        self.actions
+            .lock()
+            .unwrap()
+            .insert(String::from(action), replay);
+    }
+
+    pub(crate) fn store(
+        &self,
+        action: &str,
+        placeholders: &PlaceholderMap,
+    ) -> Result<std::path::PathBuf> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let entry = Entry {
+            action: String::from(action),
+            created: now.as_secs(),
+            placeholders: placeholders.clone(),
+        };
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let content = toml::to_string(&entry).map_err(|x| Error(x.to_string()))?;
+        // file names sort in the order the entries were created
+        let counter = self
+            .counter
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = self.path.join(format!(
+            "{:020}-{:010}.{FILE_EXTENSION}",
+            now.as_nanos(),
+            counter
+        ));
+        let temp_path = path.with_extension("tmp");
+        // the placeholders may contain sensitive data
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .and_then(|_| std::fs::rename(&temp_path, &path))
+            .map_err(|x| Error(format!("Could not write {}: {x}", path.display())))?;
+        self.in_flight.lock().unwrap().insert(path.clone());
+        Ok(path)
+    }
+
+    pub(crate) fn finish(&self, path: &std::path::Path, delivered: bool) {
+        self.in_flight.lock().unwrap().remove(path);
+        if delivered {
+            if let Err(err) = std::fs::remove_file(path) {
+                log::error!("Could not remove outbox entry {}: {err}", path.display());
+            }
+        }
+    }
+
+    // Returns the pending entries (of one action) and marks them as in flight.
+    // Entries that are too old are dropped.
+    fn take_pending(&self, action: Option<&str>) -> Vec<(std::path::PathBuf, Entry)> {
+        let mut paths: Vec<std::path::PathBuf> = match std::fs::read_dir(&self.path) {
+            Ok(dir) => dir
+                .filter_map(|x| x.ok().map(|x| x.path()))
+                .filter(|x| x.extension().is_some_and(|x| x == FILE_EXTENSION))
+                .collect(),
+            Err(err) => {
+                log::error!("Could not read outbox {}: {err}", self.path.display());
+                return Vec::new();
+            }
+        };
+        paths.sort();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let mut res = Vec::new();
+        for path in paths {
+            if in_flight.contains(&path) {
+                continue;
+            }
+            let entry = match std::fs::read_to_string(&path)
+                .map_err(|x| Error(x.to_string()))
+                .and_then(|x| toml::from_str::<Entry>(&x).map_err(|x| Error(x.to_string())))
+            {
+                Ok(entry) => entry,
+                Err(err) => {
+                    log::warn!("Dropping invalid outbox entry {}: {err}", path.display());
+                    let _ = std::fs::remove_file(&path);
+                    continue;
+                }
+            };
+            if action.is_some_and(|x| x != entry.action) {
+                continue;
+            }
+            let age = now.saturating_sub(entry.created);
+            if age > self.max_age.as_secs() {
+                log::warn!(
+                    "Dropping outbox entry of action '{}' that is {} seconds old.",
+                    entry.action,
+                    age
+                );
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            in_flight.insert(path.clone());
+            res.push((path, entry));
+        }
+        res
+    }
+
+    // Replays the pending entries (of one action) in the order they were created.
+    pub async fn replay(&self, action: Option<&str>) {
+        for (path, entry) in self.take_pending(action) {
+            let replay = self
+                .actions
+                .lock()
+                .unwrap()
+                .get(&entry.action)
+                .and_then(|x| x.upgrade());
+            match replay {
+                Some(replay) => {
+                    log::info!("Replaying outbox entry of action '{}'.", entry.action);
+                    replay.replay(path, entry.placeholders).await;
+                }
+                None => {
+                    log::warn!(
+                        "Dropping outbox entry of unknown action '{}'.",
+                        entry.action
+                    );
+                    self.finish(&path, true);
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&config::Outbox> for Outbox {
+    type Error = Error;
+
+    fn try_from(outbox: &config::Outbox) -> std::result::Result<Self, Self::Error> {
+        Self::new(
+            std::path::PathBuf::from(&outbox.path),
+            std::time::Duration::from_secs(outbox.max_age.into()),
+            std::time::Duration::from_secs(outbox.replay_interval.into()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct ReplayMock {
+        outbox: std::sync::Arc<Outbox>,
+        replayed: std::sync::Mutex<Vec<PlaceholderMap>>,
+    }
+
+    #[async_trait]
+    impl Replay for ReplayMock {
+        async fn replay(&self, path: std::path::PathBuf, placeholders: PlaceholderMap) {
+            self.replayed.lock().unwrap().push(placeholders);
+            self.outbox.finish(&path, true);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let path = std::env::temp_dir().join(format!("minmon-outbox-{}", std::process::id()));
+        let outbox = std::sync::Arc::new(
+            Outbox::new(
+                path.clone(),
+                std::time::Duration::from_secs(60),
+                std::time::Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        let replay = std::sync::Arc::new(ReplayMock {
+            outbox: outbox.clone(),
+            replayed: std::sync::Mutex::new(Vec::new()),
+        });
+        let weak = std::sync::Arc::downgrade(&replay);
+        outbox.register("Action", weak);
+        let placeholders = |x: &str| PlaceholderMap::from([(String::from("x"), String::from(x))]);
+        let first = outbox.store("Action", &placeholders("1")).unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            std::fs::metadata(&first).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        outbox.store("Action", &placeholders("2")).unwrap();
+        outbox.store("Other", &placeholders("3")).unwrap();
+        // in flight
+        outbox.replay(Some("Action")).await;
+        assert!(replay.replayed.lock().unwrap().is_empty());
+        outbox.finish(&first, false);
+        outbox.in_flight.lock().unwrap().clear();
+        let old = Entry {
+            action: String::from("Action"),
+            created: 0,
+            placeholders: placeholders("0"),
+        };
+        std::fs::write(path.join("0.toml"), toml::to_string(&old).unwrap()).unwrap();
+        outbox.replay(Some("Action")).await;
+        assert_eq!(
+            *replay.replayed.lock().unwrap(),
+            vec![placeholders("1"), placeholders("2")]
+        );
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 1);
+        // unknown actions are dropped
+        outbox.replay(None).await;
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 0);
+        std::fs::remove_dir(&path).unwrap();
+    }
+}
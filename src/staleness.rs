@@ -0,0 +1,449 @@
+use crate::action;
+use crate::alarm::{self, StateHandler};
+use crate::check;
+use crate::config;
+use crate::ActionMap;
+use crate::{duration_iso8601, Error, PlaceholderMap, Result};
+use async_trait::async_trait;
+
+pub const ALARM_NAME: &str = "Staleness";
+
+// Time of the last valid measurement data for each ID of a check.
+pub struct LastData {
+    instants: std::sync::Mutex<Vec<std::time::Instant>>,
+}
+
+impl LastData {
+    pub fn new(num_ids: usize) -> Self {
+        Self {
+            instants: std::sync::Mutex::new(vec![std::time::Instant::now(); num_ids]),
+        }
+    }
+
+    pub fn update(&self, index: usize) {
+        if let Some(instant) = self.instants.lock().unwrap().get_mut(index) {
+            *instant = std::time::Instant::now();
+        }
+    }
+
+    pub fn update_all(&self) {
+        let now = std::time::Instant::now();
+        for instant in self.instants.lock().unwrap().iter_mut() {
+            *instant = now;
+        }
+    }
+
+    fn ages(&self) -> Vec<std::time::Duration> {
+        self.instants
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| x.elapsed())
+            .collect()
+    }
+}
+
+// Runs on its own timer so it also notices checks that are stuck.
+pub struct Staleness<T = alarm::StateMachine>
+where
+    T: StateHandler,
+{
+    name: String,
+    check_name: String,
+    interval: std::time::Duration,
+    timeout: std::time::Duration,
+    ids: Vec<String>,
+    last_data: std::sync::Arc<LastData>,
+    action: std::sync::Arc<dyn action::Action>,
+    placeholders: PlaceholderMap,
+    recover_action: Option<std::sync::Arc<dyn action::Action>>,
+    recover_placeholders: PlaceholderMap,
+    state_machines: Vec<T>,
+    state_publishers: Vec<alarm::StatePublisher>,
+    silencers: Vec<alarm::Silencer>,
+}
+
+impl<T> Staleness<T>
+where
+    T: StateHandler,
+{
+    fn new(
+        check_name: String,
+        interval: std::time::Duration,
+        timeout: std::time::Duration,
+        ids: Vec<String>,
+        last_data: std::sync::Arc<LastData>,
+        action: std::sync::Arc<dyn action::Action>,
+        placeholders: PlaceholderMap,
+        recover_action: Option<std::sync::Arc<dyn action::Action>>,
+        recover_placeholders: PlaceholderMap,
+        state_machines: Vec<T>,
+        state_publishers: Vec<alarm::StatePublisher>,
+        silencers: Vec<alarm::Silencer>,
+    ) -> Result<Self> {
+        if timeout.is_zero() {
+            Err(Error(String::from("'timeout' cannot be 0.")))
+        } else {
+            Ok(Self {
+                name: format!("{check_name} ({ALARM_NAME})"),
+                check_name,
+                interval: interval.min(timeout),
+                timeout,
+                ids,
+                last_data,
+                action,
+                placeholders,
+                recover_action,
+                recover_placeholders,
+                state_machines,
+                state_publishers,
+                silencers,
+            })
+        }
+    }
+
+    async fn put_ages(&mut self, ages: Vec<std::time::Duration>, placeholders: PlaceholderMap) {
+        for ((((id, age), state_machine), state_publisher), silencer) in self
+            .ids
+            .iter()
+            .zip(ages)
+            .zip(self.state_machines.iter_mut())
+            .zip(self.state_publishers.iter())
+            .zip(self.silencers.iter())
+        {
+            let mut placeholders = placeholders.clone();
+            placeholders.insert(String::from("check_name"), self.check_name.clone());
+            placeholders.insert(String::from("check_id"), id.clone());
+            placeholders.insert(String::from("alarm_name"), String::from(ALARM_NAME));
+            placeholders.insert(String::from("data_age"), age.as_secs().to_string());
+            placeholders.insert(String::from("data_age_iso"), duration_iso8601(age));
+            crate::merge_placeholders(&mut placeholders, &self.placeholders);
+            let result = if age > self.timeout {
+                log::warn!(
+                    "Check '{}' got no data for id '{}' for {} seconds.",
+                    self.check_name,
+                    id,
+                    age.as_secs()
+                );
+                let (trigger, _) = state_machine.bad();
+                state_publisher.publish(state_machine.state());
+                if trigger && !is_silenced(silencer, &self.check_name, id) {
+                    state_machine.add_placeholders(&mut placeholders);
+                    self.action.trigger(placeholders).await
+                } else {
+                    Ok(())
+                }
+            } else {
+                let (trigger_recover, _) = state_machine.good();
+                state_publisher.publish(state_machine.state());
+                match &self.recover_action {
+                    Some(recover_action)
+                        if trigger_recover && !is_silenced(silencer, &self.check_name, id) =>
+                    {
+                        state_machine.add_placeholders(&mut placeholders);
+                        crate::merge_placeholders(&mut placeholders, &self.recover_placeholders);
+                        recover_action.trigger(placeholders).await
+                    }
+                    _ => Ok(()),
+                }
+            };
+            if let Err(err) = result {
+                log::error!(
+                    "Staleness alarm, id '{}' from check '{}' had an error: {}",
+                    id,
+                    self.check_name,
+                    err
+                );
+            }
+        }
+    }
+}
+
+fn is_silenced(silencer: &alarm::Silencer, check_name: &str, id: &str) -> bool {
+    let silenced = silencer.is_silenced();
+    if silenced {
+        log::info!(
+            "Staleness alarm, id '{id}' from check '{check_name}': Not triggering action while silenced."
+        );
+    }
+    silenced
+}
+
+#[async_trait]
+impl<T> check::Check for Staleness<T>
+where
+    T: StateHandler,
+{
+    async fn trigger(&mut self) {
+        let ages = self.last_data.ages();
+        self.put_ages(ages, crate::global_placeholders()).await;
+    }
+
+    fn interval(&self) -> std::time::Duration {
+        self.interval
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn parent(&self) -> Option<&str> {
+        Some(self.check_name.as_str())
+    }
+
+    // The check gets no data while it is disabled, which does not make it stale.
+    fn disabled(&mut self) {
+        self.last_data.update_all();
+    }
+}
+
+pub fn from_staleness_config(
+    staleness_config: &config::CheckStaleness,
+    check_config: &config::Check,
+    ids: &[String],
+    last_data: std::sync::Arc<LastData>,
+    actions: &ActionMap,
+    state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    silences: &std::sync::Arc<alarm::Silences>,
+) -> Result<Box<dyn check::Check>> {
+    let state_machines = ids
+        .iter()
+        .map(|id| {
+            alarm::StateMachine::new(
+                1,
+                staleness_config.repeat_cycles,
+                1,
+                0,
+                vec![],
+                format!(
+                    "Staleness alarm, id '{}' from check '{}'",
+                    id, check_config.name
+                ),
+            )
+        })
+        .collect::<Result<_>>()?;
+    let alarm_keys: Vec<alarm::AlarmKey> = ids
+        .iter()
+        .map(|id| alarm::AlarmKey {
+            check: check_config.name.clone(),
+            alarm: String::from(ALARM_NAME),
+            id: id.clone(),
+        })
+        .collect();
+    let state_publishers = alarm_keys
+        .iter()
+        .map(|alarm_key| alarm::StatePublisher::new(state_registry.clone(), alarm_key.clone()))
+        .collect();
+    let silencers = alarm_keys
+        .into_iter()
+        .map(|alarm_key| {
+            alarm::Silencer::new(
+                silences.clone(),
+                &check_config.maintenance_windows,
+                alarm_key,
+            )
+        })
+        .collect::<Result<_>>()?;
+    let mut placeholders = check_config.placeholders.clone();
+    crate::merge_placeholders(&mut placeholders, &staleness_config.placeholders);
+    Ok(Box::new(Staleness::new(
+        check_config.name.clone(),
+        std::time::Duration::from_secs(check_config.interval.into()),
+        std::time::Duration::from_secs(staleness_config.timeout.into()),
+        ids.to_vec(),
+        last_data,
+        action::get_action(&staleness_config.action, actions)?,
+        placeholders,
+        match &staleness_config.recover_action {
+            Some(action) => Some(action::get_action(action, actions)?),
+            None => None,
+        },
+        staleness_config.recover_placeholders.clone(),
+        state_machines,
+        state_publishers,
+        silencers,
+    )?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockall::predicate::*;
+
+    fn times_action(times: usize) -> std::sync::Arc<dyn action::Action> {
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .times(times)
+            .returning(|_| Ok(()));
+        std::sync::Arc::new(mock_action)
+    }
+
+    fn key(id: &str) -> alarm::AlarmKey {
+        alarm::AlarmKey {
+            check: String::from("Check"),
+            alarm: String::from(ALARM_NAME),
+            id: String::from(id),
+        }
+    }
+
+    fn state_publishers(
+        state_registry: &std::sync::Arc<alarm::StateRegistry>,
+    ) -> Vec<alarm::StatePublisher> {
+        ["a", "b"]
+            .into_iter()
+            .map(|id| alarm::StatePublisher::new(state_registry.clone(), key(id)))
+            .collect()
+    }
+
+    fn silencers(silences: &std::sync::Arc<alarm::Silences>) -> Vec<alarm::Silencer> {
+        ["a", "b"]
+            .into_iter()
+            .map(|id| alarm::Silencer::new(silences.clone(), &[], key(id)).unwrap())
+            .collect()
+    }
+
+    fn staleness(
+        action: std::sync::Arc<dyn action::Action>,
+        recover_action: std::sync::Arc<dyn action::Action>,
+    ) -> Staleness {
+        Staleness::new(
+            String::from("Check"),
+            std::time::Duration::from_secs(300),
+            std::time::Duration::from_secs(60),
+            vec![String::from("a"), String::from("b")],
+            std::sync::Arc::new(LastData::new(2)),
+            action,
+            PlaceholderMap::new(),
+            Some(recover_action),
+            PlaceholderMap::new(),
+            (0..2)
+                .map(|_| alarm::StateMachine::new(1, 0, 1, 0, vec![], String::from("")).unwrap())
+                .collect(),
+            state_publishers(&std::sync::Arc::new(alarm::StateRegistry::default())),
+            silencers(&std::sync::Arc::new(alarm::Silences::default())),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Staleness::<alarm::StateMachine>::new(
+            String::from("Check"),
+            std::time::Duration::from_secs(300),
+            std::time::Duration::ZERO,
+            vec![],
+            std::sync::Arc::new(LastData::new(0)),
+            times_action(0),
+            PlaceholderMap::new(),
+            None,
+            PlaceholderMap::new(),
+            vec![],
+            vec![],
+            vec![],
+        )
+        .is_err());
+        use check::Check;
+        assert_eq!(
+            staleness(times_action(0), times_action(0)).interval(),
+            std::time::Duration::from_secs(60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_ages() {
+        let mut mock_action = action::MockAction::new();
+        mock_action
+            .expect_trigger()
+            .once()
+            .with(function(|placeholders: &PlaceholderMap| {
+                assert_eq!(placeholders.get("check_name").unwrap(), "Check");
+                assert_eq!(placeholders.get("check_id").unwrap(), "b");
+                assert_eq!(placeholders.get("alarm_name").unwrap(), "Staleness");
+                assert_eq!(placeholders.get("data_age").unwrap(), "61");
+                true
+            }))
+            .returning(|_| Ok(()));
+        let mut staleness = staleness(std::sync::Arc::new(mock_action), times_action(0));
+        let seconds = std::time::Duration::from_secs;
+        staleness
+            .put_ages(vec![seconds(60), seconds(61)], PlaceholderMap::new())
+            .await;
+        staleness
+            .put_ages(vec![seconds(60), seconds(62)], PlaceholderMap::new())
+            .await;
+        staleness.recover_action = Some(times_action(1));
+        staleness
+            .put_ages(vec![seconds(60), seconds(0)], PlaceholderMap::new())
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_state_registry() {
+        let state_registry = std::sync::Arc::new(alarm::StateRegistry::default());
+        let mut staleness = staleness(times_action(1), times_action(1));
+        staleness.state_publishers = state_publishers(&state_registry);
+        let seconds = std::time::Duration::from_secs;
+        staleness
+            .put_ages(vec![seconds(0), seconds(61)], PlaceholderMap::new())
+            .await;
+        assert_eq!(
+            state_registry.matching("Check", Some(ALARM_NAME), None),
+            vec![
+                (key("a"), alarm::AlarmState::Good),
+                (key("b"), alarm::AlarmState::Bad)
+            ]
+        );
+        staleness
+            .put_ages(vec![seconds(0), seconds(0)], PlaceholderMap::new())
+            .await;
+        assert_eq!(
+            state_registry.matching("Check", Some(ALARM_NAME), Some("b")),
+            vec![(key("b"), alarm::AlarmState::Good)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_silenced() {
+        let silences = std::sync::Arc::new(alarm::Silences::default());
+        silences.add(
+            String::from("Check"),
+            Some(String::from(ALARM_NAME)),
+            Some(String::from("b")),
+            std::time::Duration::from_secs(60),
+        );
+        let mut staleness = staleness(times_action(0), times_action(0));
+        staleness.silencers = silencers(&silences);
+        staleness
+            .put_ages(
+                vec![
+                    std::time::Duration::ZERO,
+                    std::time::Duration::from_secs(61),
+                ],
+                PlaceholderMap::new(),
+            )
+            .await;
+    }
+
+    #[test]
+    fn test_disabled() {
+        use check::Check;
+        let mut staleness = staleness(times_action(0), times_action(0));
+        assert_eq!(staleness.parent(), Some("Check"));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        staleness.disabled();
+        assert!(staleness.last_data.ages()[0] < std::time::Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_last_data() {
+        let last_data = LastData::new(2);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        last_data.update(1);
+        last_data.update(2);
+        let ages = last_data.ages();
+        assert!(ages[0] > ages[1]);
+        last_data.update_all();
+        assert!(last_data.ages()[0] < std::time::Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,782 @@
+use crate::config;
+use crate::{Error, PlaceholderMap, Result};
+
+// Placeholders that are set by MinMon itself (see the docs of the checks, alarms and actions).
+const KNOWN_PLACEHOLDERS: &[&str] = &[
+    "ack_url",
+    "action_attempt",
+    "action_name",
+    "alarm_last_duration",
+    "alarm_last_duration_iso",
+    "alarm_name",
+    "alarm_shadowed_cycles",
+    "alarm_shadowed_last_duration",
+    "alarm_shadowed_last_duration_iso",
+    "alarm_shadowed_state",
+    "alarm_shadowed_timestamp",
+    "alarm_state",
+    "alarm_timestamp",
+    "baseline_mean",
+    "baseline_stddev",
+    "check_error",
+    "check_id",
+    "check_name",
+    "composite_alarms",
+    "composite_name",
+    "data",
+    "data_age",
+    "data_age_iso",
+    "data_size",
+    "data_size_bin",
+    "data_size_dec",
+    "digest",
+    "digest_count",
+    "escalation_step",
+    "event_name",
+    "failed_action",
+    "failed_error",
+    "heartbeat_exit_code",
+    "heartbeat_message",
+    "integer",
+    "level",
+    "minmon_uptime",
+    "minmon_uptime_iso",
+    "regex_match",
+    "silence_summary",
+    "state",
+    "status_code",
+    "stderr",
+    "stdout",
+    "suppressed_count",
+    "system_uptime",
+    "system_uptime_iso",
+    "temperature",
+    "time_to_full",
+    "time_to_full_iso",
+];
+
+// Placeholders with a variable part in their name.
+const KNOWN_PLACEHOLDER_PREFIXES: &[&str] = &["env:", "capture["];
+
+// Beyond this, the digits of an f64 are meaningless anyway.
+const MAX_ROUND_DECIMALS: usize = 15;
+
+#[derive(Debug, PartialEq)]
+enum Filter {
+    Default(String),
+    Upper,
+    Lower,
+    Round(usize),
+    Duration,
+    Escape(config::Escape),
+}
+
+#[derive(Debug, PartialEq)]
+struct Expression {
+    name: String,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Set(Expression),
+    NotSet(Expression),
+    Equal(Expression, String),
+    NotEqual(Expression, String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Value(Expression),
+    If(Condition, Vec<Node>, Vec<Node>),
+}
+
+// Block of the template that is currently being parsed.
+struct Frame {
+    condition: Condition,
+    // nodes before the `if` tag
+    outer: Vec<Node>,
+    // nodes of the `if` branch once the `else` tag was found
+    then: Option<Vec<Node>>,
+}
+
+enum Syntax {
+    Basic(String),
+    Extended(Vec<Node>),
+}
+
+pub struct Template {
+    syntax: Syntax,
+    // applied to the placeholder values, unless an escape filter is used
+    escape: config::Escape,
+}
+
+impl Template {
+    pub fn new(
+        template: &str,
+        syntax: config::TemplateSyntax,
+        escape: config::Escape,
+    ) -> Result<Self> {
+        let syntax = match syntax {
+            config::TemplateSyntax::Basic => Syntax::Basic(String::from(template)),
+            config::TemplateSyntax::Extended => Syntax::Extended(parse(template)?),
+        };
+        Ok(Self { syntax, escape })
+    }
+
+    pub fn fill(&self, placeholders: &PlaceholderMap) -> String {
+        match &self.syntax {
+            Syntax::Basic(template) if self.escape == config::Escape::None => {
+                crate::fill_placeholders(template, placeholders)
+            }
+            Syntax::Basic(template) => {
+                let placeholders = placeholders
+                    .iter()
+                    .map(|(k, v)| (k.clone(), escape(v, self.escape)))
+                    .collect();
+                crate::fill_placeholders(template, &placeholders)
+            }
+            Syntax::Extended(nodes) => {
+                let mut res = String::new();
+                render(nodes, placeholders, self.escape, &mut res);
+                res
+            }
+        }
+    }
+
+    // Names of the placeholders that are referenced by the template.
+    fn placeholders(&self) -> Vec<&str> {
+        match &self.syntax {
+            Syntax::Basic(template) => {
+                let mut res = Vec::new();
+                let mut rest = template.as_str();
+                while let Some(start) = rest.find("{{") {
+                    rest = &rest[start + 2..];
+                    if let Some(end) = rest.find("}}") {
+                        res.push(&rest[..end]);
+                        rest = &rest[end + 2..];
+                    }
+                }
+                res
+            }
+            Syntax::Extended(nodes) => {
+                let mut res = Vec::new();
+                collect_placeholders(nodes, &mut res);
+                res
+            }
+        }
+    }
+}
+
+fn collect_placeholders<'a>(nodes: &'a [Node], res: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Value(expression) => res.push(&expression.name),
+            Node::If(condition, then, else_) => {
+                let (Condition::Set(expression)
+                | Condition::NotSet(expression)
+                | Condition::Equal(expression, _)
+                | Condition::NotEqual(expression, _)) = condition;
+                res.push(&expression.name);
+                collect_placeholders(then, res);
+                collect_placeholders(else_, res);
+            }
+        }
+    }
+}
+
+fn parse(template: &str) -> Result<Vec<Node>> {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = template;
+    loop {
+        let start = match (rest.find("{{"), rest.find("{%")) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(a), None) | (None, Some(a)) => a,
+            (None, None) => break,
+        };
+        if start > 0 {
+            nodes.push(Node::Text(String::from(&rest[..start])));
+        }
+        let is_tag = rest[start..].starts_with("{%");
+        let close = if is_tag { "%}" } else { "}}" };
+        let inner_start = start + 2;
+        let end = rest[inner_start..]
+            .find(close)
+            .ok_or_else(|| Error(format!("Missing '{close}'.")))?;
+        let inner = rest[inner_start..inner_start + end].trim();
+        rest = &rest[inner_start + end + 2..];
+        if !is_tag {
+            nodes.push(Node::Value(parse_expression(inner)?));
+            continue;
+        }
+        let (keyword, argument) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        match (keyword, argument.trim()) {
+            ("if", condition) => stack.push(Frame {
+                condition: parse_condition(condition)?,
+                outer: std::mem::take(&mut nodes),
+                then: None,
+            }),
+            ("else", "") => {
+                let frame = stack
+                    .last_mut()
+                    .ok_or_else(|| Error(String::from("Found 'else' without 'if'.")))?;
+                if frame.then.is_some() {
+                    return Err(Error(String::from("Found more than one 'else' for 'if'.")));
+                }
+                frame.then = Some(std::mem::take(&mut nodes));
+            }
+            ("endif", "") => {
+                let frame = stack
+                    .pop()
+                    .ok_or_else(|| Error(String::from("Found 'endif' without 'if'.")))?;
+                let (then, else_) = match frame.then {
+                    Some(then) => (then, std::mem::take(&mut nodes)),
+                    None => (std::mem::take(&mut nodes), Vec::new()),
+                };
+                nodes = frame.outer;
+                nodes.push(Node::If(frame.condition, then, else_));
+            }
+            _ => return Err(Error(format!("Invalid tag '{inner}'."))),
+        }
+    }
+    if !rest.is_empty() {
+        nodes.push(Node::Text(String::from(rest)));
+    }
+    if stack.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(Error(String::from("Missing 'endif'.")))
+    }
+}
+
+// Splits at the separator unless it is part of a string literal.
+fn split_unquoted<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut res = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut last = 0;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && i >= last && text[i..].starts_with(separator) {
+            res.push(&text[last..i]);
+            last = i + separator.len();
+        }
+    }
+    res.push(&text[last..]);
+    res
+}
+
+// String literals need to be quoted, numbers can also be written without quotes.
+fn parse_literal(text: &str) -> Result<String> {
+    let text = text.trim();
+    if let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .filter(|_| text.len() >= 2)
+    {
+        let mut res = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => res.push('\n'),
+                    Some('t') => res.push('\t'),
+                    Some(c @ ('"' | '\\')) => res.push(c),
+                    _ => return Err(Error(format!("Invalid escape sequence in {text}."))),
+                }
+            } else if c == '"' {
+                return Err(Error(format!("Invalid string literal {text}.")));
+            } else {
+                res.push(c);
+            }
+        }
+        Ok(res)
+    } else if text.parse::<f64>().is_ok() {
+        Ok(String::from(text))
+    } else {
+        Err(Error(format!("Invalid literal '{text}'.")))
+    }
+}
+
+fn parse_filter(text: &str) -> Result<Filter> {
+    let (name, argument) = match text.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (text.trim(), None),
+    };
+    match (name, argument) {
+        ("default", Some(argument)) => Ok(Filter::Default(parse_literal(argument)?)),
+        ("upper", None) => Ok(Filter::Upper),
+        ("lower", None) => Ok(Filter::Lower),
+        ("round", None) => Ok(Filter::Round(0)),
+        ("round", Some(argument)) => match argument.trim().parse() {
+            Ok(decimals) if decimals <= MAX_ROUND_DECIMALS => Ok(Filter::Round(decimals)),
+            _ => Err(Error(format!(
+                "Invalid number of decimals '{}', the maximum is {MAX_ROUND_DECIMALS}.",
+                argument.trim()
+            ))),
+        },
+        ("duration", None) => Ok(Filter::Duration),
+        ("raw", None) => Ok(Filter::Escape(config::Escape::None)),
+        ("json", None) => Ok(Filter::Escape(config::Escape::Json)),
+        ("url", None) => Ok(Filter::Escape(config::Escape::Url)),
+        ("html", None) => Ok(Filter::Escape(config::Escape::Html)),
+        ("shell", None) => Ok(Filter::Escape(config::Escape::Shell)),
+        ("default", None) => Err(Error(String::from("Filter 'default' needs a value."))),
+        ("upper" | "lower" | "duration" | "raw" | "json" | "url" | "html" | "shell", Some(_)) => {
+            Err(Error(format!("Filter '{name}' does not take a value.")))
+        }
+        _ => Err(Error(format!("Unknown filter '{name}'."))),
+    }
+}
+
+fn parse_expression(text: &str) -> Result<Expression> {
+    let mut parts = split_unquoted(text, "|").into_iter();
+    let name = parts.next().unwrap_or_default().trim();
+    if name.is_empty() || name.contains(char::is_whitespace) || name.contains('"') {
+        return Err(Error(format!("Invalid placeholder name '{name}'.")));
+    }
+    Ok(Expression {
+        name: String::from(name),
+        filters: parts.map(parse_filter).collect::<Result<_>>()?,
+    })
+}
+
+fn parse_condition(text: &str) -> Result<Condition> {
+    for (operator, equal) in [("==", true), ("!=", false)] {
+        if let [left, right] = split_unquoted(text, operator)[..] {
+            let expression = parse_expression(left)?;
+            let literal = parse_literal(right)?;
+            return Ok(if equal {
+                Condition::Equal(expression, literal)
+            } else {
+                Condition::NotEqual(expression, literal)
+            });
+        }
+    }
+    match text.strip_prefix("not ") {
+        Some(text) => Ok(Condition::NotSet(parse_expression(text)?)),
+        None => Ok(Condition::Set(parse_expression(text)?)),
+    }
+}
+
+pub fn escape(value: &str, escape: config::Escape) -> String {
+    match escape {
+        config::Escape::None => String::from(value),
+        // the content of a JSON string, without the quotes
+        config::Escape::Json => {
+            let mut res = String::with_capacity(value.len());
+            for c in value.chars() {
+                match c {
+                    '"' => res.push_str("\\\""),
+                    '\\' => res.push_str("\\\\"),
+                    '\n' => res.push_str("\\n"),
+                    '\r' => res.push_str("\\r"),
+                    '\t' => res.push_str("\\t"),
+                    c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => res.push(c),
+                }
+            }
+            res
+        }
+        config::Escape::Url => {
+            let mut res = String::with_capacity(value.len());
+            for byte in value.bytes() {
+                if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+                    res.push(byte as char);
+                } else {
+                    res.push_str(&format!("%{byte:02X}"));
+                }
+            }
+            res
+        }
+        config::Escape::Html => {
+            let mut res = String::with_capacity(value.len());
+            for c in value.chars() {
+                match c {
+                    '&' => res.push_str("&amp;"),
+                    '<' => res.push_str("&lt;"),
+                    '>' => res.push_str("&gt;"),
+                    '"' => res.push_str("&quot;"),
+                    '\'' => res.push_str("&#39;"),
+                    c => res.push(c),
+                }
+            }
+            res
+        }
+        // a single argument, including the quotes
+        config::Escape::Shell => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    let units = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+    let mut remainder = seconds;
+    let mut res = Vec::new();
+    for (unit, size) in units {
+        if remainder >= size {
+            res.push(format!("{}{unit}", remainder / size));
+            remainder %= size;
+        }
+    }
+    if res.is_empty() {
+        String::from("0s")
+    } else {
+        res.join(" ")
+    }
+}
+
+impl Expression {
+    // Missing placeholders are `None` unless a default value is set.
+    fn evaluate(&self, placeholders: &PlaceholderMap) -> Option<String> {
+        let mut value = placeholders.get(&self.name).cloned();
+        for filter in self.filters.iter() {
+            value = match filter {
+                Filter::Default(default) => match value {
+                    Some(value) if !value.is_empty() => Some(value),
+                    _ => Some(default.clone()),
+                },
+                Filter::Upper => value.map(|x| x.to_uppercase()),
+                Filter::Lower => value.map(|x| x.to_lowercase()),
+                Filter::Round(decimals) => value.map(|x| match x.trim().parse::<f64>() {
+                    Ok(number) => format!("{number:.decimals$}"),
+                    Err(_) => x,
+                }),
+                Filter::Duration => value.map(|x| match x.trim().parse::<u64>() {
+                    Ok(seconds) => format_duration(seconds),
+                    Err(_) => x,
+                }),
+                Filter::Escape(mode) => value.map(|x| escape(&x, *mode)),
+            };
+        }
+        value
+    }
+}
+
+impl Condition {
+    fn evaluate(&self, placeholders: &PlaceholderMap) -> bool {
+        match self {
+            Self::Set(expression) => expression
+                .evaluate(placeholders)
+                .is_some_and(|x| !x.is_empty()),
+            Self::NotSet(expression) => expression
+                .evaluate(placeholders)
+                .is_none_or(|x| x.is_empty()),
+            Self::Equal(expression, literal) => {
+                expression.evaluate(placeholders).unwrap_or_default() == *literal
+            }
+            Self::NotEqual(expression, literal) => {
+                expression.evaluate(placeholders).unwrap_or_default() != *literal
+            }
+        }
+    }
+}
+
+fn render(
+    nodes: &[Node],
+    placeholders: &PlaceholderMap,
+    escape_mode: config::Escape,
+    res: &mut String,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => res.push_str(text),
+            Node::Value(expression) => {
+                let value = expression.evaluate(placeholders).unwrap_or_default();
+                if expression
+                    .filters
+                    .iter()
+                    .any(|x| matches!(x, Filter::Escape(_)))
+                {
+                    res.push_str(&value);
+                } else {
+                    res.push_str(&escape(&value, escape_mode));
+                }
+            }
+            Node::If(condition, then, else_) => {
+                if condition.evaluate(placeholders) {
+                    render(then, placeholders, escape_mode, res);
+                } else {
+                    render(else_, placeholders, escape_mode, res);
+                }
+            }
+        }
+    }
+}
+
+// Templates of the action, named after the config option they come from.
+fn action_templates(action: &config::Action) -> Vec<(String, &str)> {
+    match &action.type_ {
+        config::ActionType::Log(log) => vec![(String::from("template"), log.template.as_str())],
+        #[cfg(feature = "smtp")]
+        config::ActionType::Email(email) => {
+            let mut res = vec![
+                (String::from("subject"), email.subject.as_str()),
+                (String::from("body"), email.body.as_str()),
+            ];
+            if let Some(html_body) = &email.html_body {
+                res.push((String::from("html_body"), html_body.as_str()));
+            }
+            res
+        }
+        #[cfg(feature = "http")]
+        config::ActionType::Webhook(webhook) => {
+            let mut res = vec![
+                (String::from("url"), webhook.url.as_str()),
+                (String::from("body"), webhook.body.as_str()),
+            ];
+            for (name, value) in webhook.headers.iter() {
+                res.push((format!("headers.{name}"), value.as_str()));
+            }
+            res
+        }
+        #[cfg(feature = "mqtt")]
+        config::ActionType::Mqtt(mqtt) => vec![
+            (String::from("topic"), mqtt.topic.as_str()),
+            (String::from("payload"), mqtt.payload.as_str()),
+        ],
+        #[cfg(feature = "http")]
+        config::ActionType::Gotify(config::ActionGotify { title, message, .. })
+        | config::ActionType::Matrix(config::ActionMatrix { title, message, .. })
+        | config::ActionType::Ntfy(config::ActionNtfy { title, message, .. })
+        | config::ActionType::Slack(config::ActionSlack { title, message, .. })
+        | config::ActionType::Telegram(config::ActionTelegram { title, message, .. }) => vec![
+            (String::from("title"), title.as_str()),
+            (String::from("message"), message.as_str()),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+// Makes sure the templates of the actions only reference placeholders that are either set by
+// MinMon or defined somewhere in the config.
+pub fn validate(config: &config::Config) -> Result<()> {
+    let mut maps: Vec<&PlaceholderMap> = vec![&config.report.placeholders];
+    maps.extend(config.report.events.iter().map(|x| &x.placeholders));
+    maps.extend(config.actions.iter().map(|x| &x.placeholders));
+    for check in config.checks.iter() {
+        maps.push(&check.placeholders);
+        if let Some(staleness) = &check.staleness {
+            maps.extend([&staleness.placeholders, &staleness.recover_placeholders]);
+        }
+        for alarm in check.alarms.iter() {
+            maps.extend([
+                &alarm.placeholders,
+                &alarm.recover_placeholders,
+                &alarm.error_placeholders,
+                &alarm.error_recover_placeholders,
+            ]);
+            maps.extend(alarm.escalation.iter().map(|x| &x.placeholders));
+        }
+    }
+    for composite in config.composite_alarms.iter() {
+        maps.extend([&composite.placeholders, &composite.recover_placeholders]);
+    }
+    let known: std::collections::HashSet<&str> = KNOWN_PLACEHOLDERS
+        .iter()
+        .copied()
+        .chain(maps.iter().flat_map(|x| x.keys().map(|x| x.as_str())))
+        .collect();
+    for action in config.actions.iter().filter(|x| !x.disable) {
+        for (option, text) in action_templates(action) {
+            let template = Template::new(text, action.template_syntax, config::Escape::None)
+                .map_err(|x| {
+                    Error(format!(
+                        "Invalid '{option}' of action '{}': {x}",
+                        action.name
+                    ))
+                })?;
+            for name in template.placeholders() {
+                // only the environment variables with the prefix are passed to the templates
+                if let Some(env_var) = name.strip_prefix("env:") {
+                    if !env_var.starts_with(&config.general.env_var_prefix) {
+                        return Err(Error(format!(
+                            "Action '{}' references environment variable '{env_var}' in '{option}' that does not start with '{}'.",
+                            action.name, config.general.env_var_prefix
+                        )));
+                    }
+                } else if !known.contains(name)
+                    && !KNOWN_PLACEHOLDER_PREFIXES
+                        .iter()
+                        .any(|x| name.starts_with(x))
+                {
+                    return Err(Error(format!(
+                        "Action '{}' references unknown placeholder '{name}' in '{option}'.",
+                        action.name
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fill(template: &str, placeholders: &[(&str, &str)]) -> String {
+        let placeholders = placeholders
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect();
+        Template::new(
+            template,
+            config::TemplateSyntax::Extended,
+            config::Escape::None,
+        )
+        .unwrap()
+        .fill(&placeholders)
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill("X{{A}}{{ missing }}Z", &[("A", "Y")]), "XYZ");
+        assert_eq!(fill(r#"{{ A | default: "n/a" }}"#, &[]), "n/a");
+        assert_eq!(fill(r#"{{ A | default: "n/a" }}"#, &[("A", "")]), "n/a");
+        assert_eq!(fill(r#"{{A|default:"a|b"|upper}}"#, &[]), "A|B");
+        assert_eq!(fill("{{ A | lower }}", &[("A", "Bad")]), "bad");
+        assert_eq!(fill("{{ A | round: 2 }}", &[("A", "3.14159")]), "3.14");
+        assert_eq!(fill("{{ A | round }}", &[("A", "foo")]), "foo");
+        assert_eq!(fill("{{ A | duration }}", &[("A", "93784")]), "1d 2h 3m 4s");
+        assert_eq!(fill("{{ A | duration }}", &[("A", "0")]), "0s");
+        assert_eq!(fill("{{env:MINMON_X}}", &[("env:MINMON_X", "Y")]), "Y");
+    }
+
+    #[test]
+    fn test_escape() {
+        let value = "a \"b\"\\\n<c> & 'd'";
+        assert_eq!(
+            escape(value, config::Escape::Json),
+            "a \\\"b\\\"\\\\\\n<c> & 'd'"
+        );
+        assert_eq!(
+            escape(value, config::Escape::Url),
+            "a%20%22b%22%5C%0A%3Cc%3E%20%26%20%27d%27"
+        );
+        assert_eq!(
+            escape(value, config::Escape::Html),
+            "a &quot;b&quot;\\\n&lt;c&gt; &amp; &#39;d&#39;"
+        );
+        assert_eq!(escape("it's", config::Escape::Shell), "'it'\\''s'");
+        let placeholders = PlaceholderMap::from([(String::from("A"), String::from("\"x\""))]);
+        let fill = |template: &str, syntax| {
+            Template::new(template, syntax, config::Escape::Json)
+                .unwrap()
+                .fill(&placeholders)
+        };
+        assert_eq!(fill("{{A}}", config::TemplateSyntax::Basic), "\\\"x\\\"");
+        assert_eq!(fill("{{A}}", config::TemplateSyntax::Extended), "\\\"x\\\"");
+        assert_eq!(
+            fill("{{A | raw}}", config::TemplateSyntax::Extended),
+            "\"x\""
+        );
+        assert_eq!(
+            fill("{{A | url}}", config::TemplateSyntax::Extended),
+            "%22x%22"
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let template = r#"{% if alarm_state == "Bad" %}down{% if data %} ({{data}}){% endif %}{% else %}up{% endif %}!"#;
+        assert_eq!(fill(template, &[("alarm_state", "Bad")]), "down!");
+        assert_eq!(
+            fill(template, &[("alarm_state", "Bad"), ("data", "95%")]),
+            "down (95%)!"
+        );
+        assert_eq!(fill(template, &[("alarm_state", "Good")]), "up!");
+        assert_eq!(fill("{% if not A %}none{% endif %}", &[]), "none");
+        assert_eq!(fill(r#"{% if A != "1" %}x{% endif %}"#, &[("A", "1")]), "");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for template in [
+            "{{ A",
+            "{{ }}",
+            "{{ A | foo }}",
+            "{{ A | default }}",
+            "{{ A | round: x }}",
+            "{{ A | round: 16 }}",
+            "{% if A %}",
+            "{% endif %}",
+            "{% else %}",
+            "{% if A %}{% else %}{% else %}{% endif %}",
+            "{% for x in A %}",
+            r#"{% if A == B %}{% endif %}"#,
+        ] {
+            assert!(
+                Template::new(
+                    template,
+                    config::TemplateSyntax::Extended,
+                    config::Escape::None
+                )
+                .is_err(),
+                "{template}"
+            );
+        }
+        // basic templates are not parsed
+        assert!(Template::new("{{ A", config::TemplateSyntax::Basic, config::Escape::None).is_ok());
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let template = Template::new(
+            "{{A}}{% if B %}{{C|upper}}{% else %}{{D}}{% endif %}",
+            config::TemplateSyntax::Extended,
+            config::Escape::None,
+        )
+        .unwrap();
+        assert_eq!(template.placeholders(), vec!["A", "B", "C", "D"]);
+        let template = Template::new(
+            "{{A}} {{B}",
+            config::TemplateSyntax::Basic,
+            config::Escape::None,
+        )
+        .unwrap();
+        assert_eq!(template.placeholders(), vec!["A"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let validate = |template: &str| {
+            let config = config::Config::try_from(
+                format!(
+                    r#"
+                    [[actions]]
+                    name = "Log"
+                    type = "Log"
+                    template_syntax = "Extended"
+                    template = '{template}'
+
+                    [[checks]]
+                    name = "Check"
+                    type = "MemoryUsage"
+                    placeholders = {{"hostname" = "foo"}}
+
+                    [[checks.alarms]]
+                    name = "Alarm"
+                    action = "Log"
+                    level = 90
+                    "#
+                )
+                .as_str(),
+            )
+            .unwrap();
+            validate(&config)
+        };
+        assert!(validate("{{check_name}} {{hostname}} {{env:MINMON_X}} {{capture[0]}}").is_ok());
+        assert!(validate("{% if alarm_state %}{{ level | round }}{% endif %}").is_ok());
+        assert!(validate("{{hostnam}}").is_err());
+        assert!(validate("{{env:HOME}}").is_err());
+        assert!(validate("{% if foo %}{% endif %}").is_err());
+    }
+}